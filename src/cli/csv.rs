@@ -1,47 +1,790 @@
 use std::{
     fmt::{self, Display, Formatter},
+    io::{IsTerminal, Write},
     str::FromStr,
 };
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgAction, ArgGroup, Args, Parser, Subcommand};
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
 
-use crate::{process_csv, CmdExector};
+use crate::{
+    nest_records, print_paged, process_csv, process_csv_diff, process_csv_group, process_csv_join,
+    process_csv_merge, process_csv_query, process_csv_render, process_csv_render_rows,
+    process_csv_show, process_csv_split, process_csv_stats, process_csv_to_sqlite, read_records,
+    transform_records, write_records, CmdExector, RejectSink, SplitBy,
+};
 
 use super::verify_file;
 
+/// Table name for a CSV file, e.g. `assets/juventus.csv` -> `juventus`
+pub fn table_name(path: &str) -> String {
+    if path == "-" {
+        return "stdin".to_string();
+    }
+    std::path::Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "stdin".to_string())
+}
+
+/// Default file name prefix of `csv split`, a glob pattern or stdin has no
+/// file name to take it from
+fn split_prefix(input: &str) -> String {
+    if input == "-" || input.contains(['*', '?', '[']) {
+        "split".to_string()
+    } else {
+        table_name(input)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     Json,
     Yaml,
+    Toml,
+    Ndjson,
+    Csv,
+    Table,
+    Markdown,
+    Html,
+    Latex,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InputFormat {
+    Csv,
+    Json,
+    Yaml,
+    Toml,
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CsvQuoteStyle {
+    Always,
+    Necessary,
+    NonNumeric,
+    Never,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub desc: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggOp {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregation {
+    pub op: AggOp,
+    pub column: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    Inner,
+    Left,
+    Right,
+    Outer,
+}
+
+/// Text encoding of CSV input, `Auto` sniffs the BOM and guesses from the content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvEncoding {
+    Auto,
+    Label(&'static Encoding),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    Strict,
+    Skip,
+    Report,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DiffFormat {
+    Text,
+    Json,
 }
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file, help = "输入 CSV 文件")]
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "输入文件，- 为标准输入")]
     pub input: String,
 
-    #[arg(short, long, help = "输出文件")]
+    #[arg(long, value_parser = parse_input_format, default_value = "csv", help = "输入文件格式，非 CSV 输入须为对象数组")]
+    pub input_format: InputFormat,
+
+    #[arg(short, long, help = "输出文件，- 为标准输出")]
     pub output: Option<String>,
 
     #[arg(short, long, value_parser = parse_format, default_value = "json", help = "输出文件格式")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',', help = "CSV 文件的分隔符")]
-    pub delimiter: char,
+    #[arg(long, value_parser = verify_file, help = "用 JSON Schema 校验每一行输出，有不合法的行时以非零状态退出")]
+    pub json_schema: Option<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+
+    #[command(flatten)]
+    pub writer: CsvWriterOpts,
+}
+
+#[derive(Debug, Subcommand)]
+#[enum_dispatch(CmdExector)]
+pub enum CsvSubCommand {
+    #[command(name = "show", about = "Show CSV as an aligned table")]
+    Show(CsvShowOpts),
+
+    #[command(name = "group", about = "Group rows and compute count/sum/min/max/avg")]
+    Group(CsvGroupOpts),
+
+    #[command(name = "stats", about = "Profile every column of a CSV")]
+    Stats(CsvStatsOpts),
+
+    #[command(name = "join", about = "Join two CSV files on key columns")]
+    Join(CsvJoinOpts),
+
+    #[command(
+        name = "diff",
+        about = "Compare two CSV files by key, exit with 0 when they match, 1 when they differ and 2 on errors"
+    )]
+    Diff(CsvDiffOpts),
+
+    #[command(name = "query", about = "Run SQL over CSV files registered as tables")]
+    Query(CsvQueryOpts),
+
+    #[command(
+        name = "to-sqlite",
+        about = "Load CSV into a table of a SQLite database file"
+    )]
+    ToSqlite(CsvToSqliteOpts),
+
+    #[command(
+        name = "split",
+        about = "Split CSV into files of N rows or one file per column value"
+    )]
+    Split(CsvSplitOpts),
+
+    #[command(
+        name = "merge",
+        about = "Concatenate CSV files, aligning columns by header"
+    )]
+    Merge(CsvMergeOpts),
+
+    #[command(
+        name = "render",
+        about = "Render a Jinja-like template once per row or once for all rows"
+    )]
+    Render(CsvRenderOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvShowOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "输入文件，- 为标准输入")]
+    pub input: String,
+
+    #[arg(long, conflicts_with = "tail", help = "只显示前 N 行")]
+    pub head: Option<usize>,
+
+    #[arg(long, help = "只显示后 N 行")]
+    pub tail: Option<usize>,
+
+    #[arg(long, default_value_t = 40, help = "单元格最大显示宽度，超出部分截断")]
+    pub max_width: usize,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvGroupOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "输入文件，- 为标准输入")]
+    pub input: String,
+
+    #[arg(
+        long,
+        required = true,
+        value_delimiter = ',',
+        help = "分组的列，如 Nationality"
+    )]
+    pub by: Vec<String>,
+
+    #[arg(long = "agg", value_parser = parse_aggregation, help = "聚合，如 \"avg:Kit Number\"，可选 count/sum/min/max/avg，可多次指定")]
+    pub aggs: Vec<Aggregation>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    // applied to the grouped rows, e.g. `--sort-by count:desc`
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+
+    #[command(flatten)]
+    pub output: CsvOutputOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "输入文件，- 为标准输入")]
+    pub input: String,
+
+    #[arg(long, default_value_t = 5, help = "每列显示出现次数最多的前 K 个值")]
+    pub top: usize,
+
+    #[arg(short, long, default_value = "-", help = "输出文件，默认为标准输出")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, default_value = "table", help = "输出文件格式")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub writer: CsvWriterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
+    #[arg(long, value_parser = verify_file, help = "左侧 CSV 文件")]
+    pub left: String,
+
+    #[arg(long, value_parser = verify_file, help = "右侧 CSV 文件")]
+    pub right: String,
+
+    #[arg(
+        long,
+        required = true,
+        value_delimiter = ',',
+        help = "连接的键列，两侧列名须相同"
+    )]
+    pub on: Vec<String>,
+
+    #[arg(long = "type", value_parser = parse_join_type, default_value = "inner", help = "连接方式：inner/left/right/outer")]
+    pub how: JoinType,
+
+    #[arg(long, default_value = "_left", help = "两侧列名冲突时左侧列的后缀")]
+    pub left_suffix: String,
+
+    #[arg(long, default_value = "_right", help = "两侧列名冲突时右侧列的后缀")]
+    pub right_suffix: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    // applied to the joined rows
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+
+    #[command(flatten)]
+    pub output: CsvOutputOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_file, help = "旧版本 CSV 文件")]
+    pub old: String,
+
+    #[arg(value_parser = verify_file, help = "新版本 CSV 文件")]
+    pub new: String,
+
+    #[arg(
+        short,
+        long,
+        required = true,
+        value_delimiter = ',',
+        help = "用于匹配行的键列"
+    )]
+    pub key: Vec<String>,
+
+    #[arg(short, long, value_parser = parse_diff_format, default_value = "text", help = "输出格式：text/json")]
+    pub format: DiffFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvQueryOpts {
+    #[arg(
+        help = "SQL 语句，如 \"SELECT Nationality, count(*) FROM juventus GROUP BY Nationality\""
+    )]
+    pub sql: String,
+
+    #[arg(short, long = "table", required = true, value_parser = parse_table, help = "注册为表的 CSV 文件，NAME=PATH 或 PATH（表名为文件名），可多次指定")]
+    pub tables: Vec<(String, String)>,
+
+    #[arg(short, long, default_value = "-", help = "输出文件，默认为标准输出")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, default_value = "table", help = "输出文件格式")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub writer: CsvWriterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvToSqliteOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "输入文件，- 为标准输入（只读一遍，列不声明类型）")]
+    pub input: String,
+
+    #[arg(short, long, help = "SQLite 数据库文件，不存在时创建")]
+    pub output: String,
+
+    #[arg(short, long, help = "表名，默认为输入文件名")]
+    pub table: Option<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "为这些列创建索引，如 Name,Nationality"
+    )]
+    pub index: Vec<String>,
+
+    #[arg(long, help = "表已存在时先删除")]
+    pub replace: bool,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+}
+
+#[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("by").required(true).args(["rows", "by_column"])))]
+pub struct CsvSplitOpts {
+    #[arg(
+        default_value = "-",
+        help = "输入文件，支持 data/*.csv 这样的通配符，多个文件按列名合并后再拆分"
+    )]
+    pub inputs: Vec<String>,
+
+    #[arg(long, help = "每个文件的行数")]
+    pub rows: Option<usize>,
+
+    #[arg(long, help = "按这一列的值拆分，每个值一个文件")]
+    pub by_column: Option<String>,
+
+    #[arg(long, default_value = ".", help = "输出目录")]
+    pub output_dir: String,
+
+    #[arg(
+        long,
+        help = "输出文件名前缀，默认为第一个输入文件名，输入为通配符或标准输入时为 split"
+    )]
+    pub prefix: Option<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+
+    #[command(flatten)]
+    pub writer: CsvWriterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMergeOpts {
+    #[arg(required = true, help = "输入文件，支持 data/*.csv 这样的通配符")]
+    pub inputs: Vec<String>,
+
+    #[arg(short, long, default_value = "-", help = "输出文件，默认为标准输出")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, default_value = "csv", help = "输出文件格式")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+
+    #[command(flatten)]
+    pub writer: CsvWriterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvRenderOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "输入文件，- 为标准输入")]
+    pub input: String,
+
+    #[arg(short, long, value_parser = verify_file, help = "模板文件，语法同 Jinja，支持循环、条件和过滤器")]
+    pub template: String,
+
+    #[arg(
+        long,
+        help = "每行渲染一次，输出到按该模板生成的路径，如 \"out/{{Name}}.txt\""
+    )]
+    pub output_pattern: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        conflicts_with = "output_pattern",
+        help = "整个数据集渲染一次（变量 rows、headers）的输出文件，默认为标准输出"
+    )]
+    pub output: String,
+
+    #[arg(long, help = "按 address.city、tags[0] 这样的列名生成嵌套的对象和数组")]
+    pub nest: bool,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub transform: CsvTransformOpts,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CsvOutputOpts {
+    #[arg(short, long, default_value = "-", help = "输出文件，默认为标准输出")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, default_value = "json", help = "输出文件格式")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub writer: CsvWriterOpts,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CsvReaderOpts {
+    #[arg(short, long, value_parser = parse_delimiter, default_value = ",", help = "CSV 文件的分隔符")]
+    pub delimiter: u8,
 
-    #[arg(long, default_value_t = true, help = "CSV 是否包含标题行")]
+    #[arg(long, default_value_t = true, action = ArgAction::Set, help = "CSV 是否包含标题行")]
     pub header: bool,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "列名，无标题行时默认为 col1..colN"
+    )]
+    pub columns: Option<Vec<String>>,
+
+    #[arg(long, help = "推断单元格类型：整数、浮点数、布尔值、日期，空值为 null")]
+    pub infer_types: bool,
+
+    #[arg(long, value_parser = verify_file, help = "按列指定类型的 schema 文件 (YAML/JSON)")]
+    pub schema: Option<String>,
+
+    #[arg(long, value_parser = parse_on_error, default_value = "strict", help = "遇到错误行时：strict 中止，skip 跳过，report 跳过并记录到 --reject-file")]
+    pub on_error: OnError,
+
+    #[arg(
+        long,
+        default_value = "rejected.csv",
+        help = "report 模式下记录被拒绝行的文件，file 列是所在的输入文件，输入经过转码时偏移记录在 decoded_byte 列，是转码后 UTF-8 文本中的偏移"
+    )]
+    pub reject_file: String,
+
+    #[arg(long, value_parser = parse_encoding, default_value = "auto", help = "输入文件编码，如 utf-8、gbk、gb18030、utf-16le，auto 为自动检测")]
+    pub encoding: CsvEncoding,
+
+    /// Shared by every input of the command so the reject file is only created once
+    #[arg(skip)]
+    pub rejects: RejectSink,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct CsvTransformOpts {
+    #[arg(long, value_delimiter = ',', help = "只保留这些列，如 Name,Position")]
+    pub select: Option<Vec<String>>,
+
+    #[arg(long, value_parser = parse_rename, help = "重命名列，如 \"Kit Number=kit\"，可多次指定")]
+    pub rename: Vec<(String, String)>,
+
+    #[arg(
+        long = "where",
+        help = "过滤行，如 'Position == \"Forward\" && Kit Number > 9'"
+    )]
+    pub filter: Option<String>,
+
+    #[arg(long, value_parser = parse_sort_key, value_delimiter = ',', help = "按列排序，如 \"Kit Number:desc\"，数字排在文本前，空值在最后")]
+    pub sort_by: Vec<SortKey>,
+
+    #[arg(long, value_delimiter = ',', help = "按列去重，保留第一次出现的行")]
+    pub unique_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CsvWriterOpts {
+    #[arg(long, default_value = "records", help = "TOML 输入输出时数组表的键名")]
+    pub toml_key: String,
+
+    #[arg(long, value_parser = parse_delimiter, default_value = ",", help = "CSV 输出时的分隔符")]
+    pub output_delimiter: u8,
+
+    #[arg(long, value_parser = parse_quote_style, default_value = "necessary", help = "CSV 输出时的引号风格")]
+    pub quote_style: CsvQuoteStyle,
+
+    #[arg(long, default_value_t = 40, help = "表格输出时单元格最大显示宽度")]
+    pub max_width: usize,
+
+    #[arg(long, help = "按 address.city、tags[0] 这样的列名生成嵌套的对象和数组")]
+    pub nest: bool,
+
+    #[arg(long, help = "HTML 输出时生成包含样式的完整页面")]
+    pub standalone: bool,
 }
 
 impl CmdExector for CsvOpts {
     async fn execute(self) -> Result<()> {
+        if let Some(cmd) = self.cmd {
+            return cmd.execute().await;
+        }
         let output = if let Some(output) = self.output {
             output
         } else {
-            format!("output.{}", self.format)
+            format!("output.{}", self.format.extension())
         };
-        process_csv(&self.input, output, self.format)
+        process_csv(
+            &self.input,
+            self.input_format,
+            output,
+            self.format,
+            &self.reader,
+            &self.transform,
+            &self.writer,
+            self.json_schema.as_deref(),
+        )
+    }
+}
+
+impl CmdExector for CsvShowOpts {
+    async fn execute(self) -> Result<()> {
+        let table = process_csv_show(
+            &self.input,
+            &self.reader,
+            &self.transform,
+            self.head,
+            self.tail,
+            self.max_width.max(2),
+        )?;
+        print_paged(&table)
+    }
+}
+
+impl CmdExector for CsvGroupOpts {
+    async fn execute(self) -> Result<()> {
+        let records = process_csv_group(
+            &self.input,
+            &self.reader,
+            &self.by,
+            &self.aggs,
+            &self.transform,
+        )?;
+        let CsvOutputOpts {
+            output,
+            format,
+            writer,
+        } = &self.output;
+        write_records(records, output, *format, writer)
+    }
+}
+
+impl CmdExector for CsvStatsOpts {
+    async fn execute(self) -> Result<()> {
+        let records = process_csv_stats(&self.input, &self.reader, self.top)?;
+        write_records(records, &self.output, self.format, &self.writer)
+    }
+}
+
+impl CmdExector for CsvJoinOpts {
+    async fn execute(self) -> Result<()> {
+        let records = process_csv_join(
+            &self.left,
+            &self.right,
+            &self.reader,
+            &self.on,
+            self.how,
+            (&self.left_suffix, &self.right_suffix),
+            &self.transform,
+        )?;
+        let CsvOutputOpts {
+            output,
+            format,
+            writer,
+        } = &self.output;
+        write_records(records, output, *format, writer)
+    }
+}
+
+impl CmdExector for CsvDiffOpts {
+    async fn execute(self) -> Result<()> {
+        // like diff(1), so scripts can tell changed data from a failed run
+        match self.diff() {
+            Ok(false) => Ok(()),
+            Ok(true) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+impl CsvDiffOpts {
+    /// Print the differences, returns whether there are any
+    fn diff(&self) -> Result<bool> {
+        let diff = process_csv_diff(&self.old, &self.new, &self.reader, &self.key)?;
+        match self.format {
+            DiffFormat::Text => {
+                let color = std::io::stdout().is_terminal();
+                print!("{}", diff.to_text(&self.key, color));
+            }
+            DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+        }
+        std::io::stdout().flush()?;
+        Ok(!diff.is_empty())
+    }
+}
+
+impl CmdExector for CsvQueryOpts {
+    async fn execute(self) -> Result<()> {
+        let records = process_csv_query(&self.sql, &self.tables, &self.reader)?;
+        write_records(records, &self.output, self.format, &self.writer)
+    }
+}
+
+impl CmdExector for CsvToSqliteOpts {
+    async fn execute(self) -> Result<()> {
+        let table = self.table.unwrap_or_else(|| table_name(&self.input));
+        let count = process_csv_to_sqlite(
+            &self.input,
+            &self.output,
+            &table,
+            &self.index,
+            self.replace,
+            &self.reader,
+            &self.transform,
+        )?;
+        eprintln!("Loaded {} rows into {} ({})", count, table, self.output);
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvSplitOpts {
+    async fn execute(self) -> Result<()> {
+        let by = match (self.rows, self.by_column) {
+            (Some(rows), _) => SplitBy::Rows(rows),
+            (None, Some(column)) => SplitBy::Column(column),
+            (None, None) => unreachable!("clap requires --rows or --by-column"),
+        };
+        let prefix = self.prefix.unwrap_or_else(|| split_prefix(&self.inputs[0]));
+        let records = process_csv_merge(&self.inputs, &self.reader)?;
+        let records = transform_records(records, &self.transform)?;
+        let files = process_csv_split(records, &by, &self.output_dir, &prefix, &self.writer)?;
+        for (file, rows) in files {
+            eprintln!("{}: {} rows", file, rows);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvMergeOpts {
+    async fn execute(self) -> Result<()> {
+        let records = process_csv_merge(&self.inputs, &self.reader)?;
+        let records = transform_records(records, &self.transform)?;
+        write_records(records, &self.output, self.format, &self.writer)
+    }
+}
+
+impl CmdExector for CsvRenderOpts {
+    async fn execute(self) -> Result<()> {
+        let template = std::fs::read_to_string(&self.template)?;
+        let records = read_records(&self.input, InputFormat::Csv, &self.reader, "")?;
+        let records = transform_records(records, &self.transform)?;
+        let records = if self.nest {
+            nest_records(records)?
+        } else {
+            records
+        };
+        match &self.output_pattern {
+            Some(pattern) => {
+                let files = process_csv_render_rows(records, &template, pattern)?;
+                eprintln!("Rendered {} files", files.len());
+                Ok(())
+            }
+            None => process_csv_render(records, &template, &self.output),
+        }
+    }
+}
+
+impl Default for CsvReaderOpts {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            header: true,
+            columns: None,
+            infer_types: false,
+            schema: None,
+            on_error: OnError::Strict,
+            reject_file: "rejected.csv".to_string(),
+            encoding: CsvEncoding::Auto,
+            rejects: Default::default(),
+        }
+    }
+}
+
+impl Default for CsvWriterOpts {
+    fn default() -> Self {
+        Self {
+            toml_key: "records".to_string(),
+            output_delimiter: b',',
+            quote_style: CsvQuoteStyle::Necessary,
+            max_width: 40,
+            nest: false,
+            standalone: false,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Default extension of the output file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Table => "txt",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+            OutputFormat::Latex => "tex",
+        }
     }
 }
 
@@ -49,11 +792,99 @@ fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse()
 }
 
+fn parse_input_format(format: &str) -> Result<InputFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_quote_style(style: &str) -> Result<CsvQuoteStyle, anyhow::Error> {
+    style.parse()
+}
+
+fn parse_encoding(encoding: &str) -> Result<CsvEncoding, anyhow::Error> {
+    encoding.parse()
+}
+
+fn parse_on_error(mode: &str) -> Result<OnError, anyhow::Error> {
+    mode.parse()
+}
+
+fn parse_rename(rename: &str) -> Result<(String, String), anyhow::Error> {
+    match rename.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+            Ok((from.to_string(), to.to_string()))
+        }
+        _ => anyhow::bail!("Invalid rename: {}, expected OLD=NEW", rename),
+    }
+}
+
+fn parse_diff_format(format: &str) -> Result<DiffFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_table(table: &str) -> Result<(String, String), anyhow::Error> {
+    let (name, path) = match table.split_once('=') {
+        Some((name, path)) => (name.to_string(), path),
+        None => (table_name(table), table),
+    };
+    let path = verify_file(path).map_err(|e| anyhow::anyhow!(e))?;
+    Ok((name, path))
+}
+
+fn parse_join_type(how: &str) -> Result<JoinType, anyhow::Error> {
+    how.parse()
+}
+
+fn parse_sort_key(key: &str) -> Result<SortKey, anyhow::Error> {
+    let (column, desc) = match key.rsplit_once(':') {
+        Some((column, "desc")) => (column, true),
+        Some((column, "asc")) => (column, false),
+        _ => (key, false),
+    };
+    if column.is_empty() {
+        anyhow::bail!("Invalid sort key: {}", key);
+    }
+    Ok(SortKey {
+        column: column.to_string(),
+        desc,
+    })
+}
+
+fn parse_aggregation(agg: &str) -> Result<Aggregation, anyhow::Error> {
+    let Some((op, column)) = agg.split_once(':') else {
+        anyhow::bail!("Invalid aggregation: {}, expected OP:COLUMN", agg);
+    };
+    if column.is_empty() {
+        anyhow::bail!("Invalid aggregation: {}, expected OP:COLUMN", agg);
+    }
+    Ok(Aggregation {
+        op: op.parse()?,
+        column: column.to_string(),
+    })
+}
+
+fn parse_delimiter(delimiter: &str) -> Result<u8, anyhow::Error> {
+    match delimiter {
+        "\\t" | "tab" => Ok(b'\t'),
+        s if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => anyhow::bail!(
+            "Invalid delimiter: {}, must be a single ASCII character",
+            delimiter
+        ),
+    }
+}
+
 impl From<OutputFormat> for &'static str {
     fn from(format: OutputFormat) -> Self {
         match format {
             OutputFormat::Json => "json",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Table => "table",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Latex => "latex",
         }
     }
 }
@@ -65,6 +896,13 @@ impl FromStr for OutputFormat {
         match s {
             "json" => Ok(OutputFormat::Json),
             "yaml" => Ok(OutputFormat::Yaml),
+            "toml" => Ok(OutputFormat::Toml),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            "latex" => Ok(OutputFormat::Latex),
             _ => anyhow::bail!("Invalid format: {}", s),
         }
     }
@@ -75,3 +913,280 @@ impl Display for OutputFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl From<InputFormat> for &'static str {
+    fn from(format: InputFormat) -> Self {
+        match format {
+            InputFormat::Csv => "csv",
+            InputFormat::Json => "json",
+            InputFormat::Yaml => "yaml",
+            InputFormat::Toml => "toml",
+            InputFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "json" => Ok(InputFormat::Json),
+            "yaml" => Ok(InputFormat::Yaml),
+            "toml" => Ok(InputFormat::Toml),
+            "ndjson" => Ok(InputFormat::Ndjson),
+            _ => anyhow::bail!("Invalid input format: {}", s),
+        }
+    }
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<DiffFormat> for &'static str {
+    fn from(format: DiffFormat) -> Self {
+        match format {
+            DiffFormat::Text => "text",
+            DiffFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            _ => anyhow::bail!("Invalid diff format: {}", s),
+        }
+    }
+}
+
+impl Display for DiffFormat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<JoinType> for &'static str {
+    fn from(how: JoinType) -> Self {
+        match how {
+            JoinType::Inner => "inner",
+            JoinType::Left => "left",
+            JoinType::Right => "right",
+            JoinType::Outer => "outer",
+        }
+    }
+}
+
+impl FromStr for JoinType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inner" => Ok(JoinType::Inner),
+            "left" => Ok(JoinType::Left),
+            "right" => Ok(JoinType::Right),
+            "outer" => Ok(JoinType::Outer),
+            _ => anyhow::bail!("Invalid join type: {}", s),
+        }
+    }
+}
+
+impl Display for JoinType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<AggOp> for &'static str {
+    fn from(op: AggOp) -> Self {
+        match op {
+            AggOp::Count => "count",
+            AggOp::Sum => "sum",
+            AggOp::Min => "min",
+            AggOp::Max => "max",
+            AggOp::Avg => "avg",
+        }
+    }
+}
+
+impl FromStr for AggOp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "count" => Ok(AggOp::Count),
+            "sum" => Ok(AggOp::Sum),
+            "min" => Ok(AggOp::Min),
+            "max" => Ok(AggOp::Max),
+            "avg" => Ok(AggOp::Avg),
+            _ => anyhow::bail!("Invalid aggregation: {}", s),
+        }
+    }
+}
+
+impl Display for AggOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl Display for Aggregation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}({})", self.op, self.column)
+    }
+}
+
+impl From<CsvQuoteStyle> for &'static str {
+    fn from(style: CsvQuoteStyle) -> Self {
+        match style {
+            CsvQuoteStyle::Always => "always",
+            CsvQuoteStyle::Necessary => "necessary",
+            CsvQuoteStyle::NonNumeric => "non-numeric",
+            CsvQuoteStyle::Never => "never",
+        }
+    }
+}
+
+impl FromStr for CsvQuoteStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(CsvQuoteStyle::Always),
+            "necessary" => Ok(CsvQuoteStyle::Necessary),
+            "non-numeric" => Ok(CsvQuoteStyle::NonNumeric),
+            "never" => Ok(CsvQuoteStyle::Never),
+            _ => anyhow::bail!("Invalid quote style: {}", s),
+        }
+    }
+}
+
+impl Display for CsvQuoteStyle {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<CsvEncoding> for &'static str {
+    fn from(encoding: CsvEncoding) -> Self {
+        match encoding {
+            CsvEncoding::Auto => "auto",
+            CsvEncoding::Label(encoding) => encoding.name(),
+        }
+    }
+}
+
+impl FromStr for CsvEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(CsvEncoding::Auto);
+        }
+        match Encoding::for_label(s.as_bytes()) {
+            Some(encoding) => Ok(CsvEncoding::Label(encoding)),
+            None => anyhow::bail!("Invalid encoding: {}", s),
+        }
+    }
+}
+
+impl Display for CsvEncoding {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<OnError> for &'static str {
+    fn from(mode: OnError) -> Self {
+        match mode {
+            OnError::Strict => "strict",
+            OnError::Skip => "skip",
+            OnError::Report => "report",
+        }
+    }
+}
+
+impl FromStr for OnError {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(OnError::Strict),
+            "skip" => Ok(OnError::Skip),
+            "report" => Ok(OnError::Report),
+            _ => anyhow::bail!("Invalid error mode: {}", s),
+        }
+    }
+}
+
+impl Display for OnError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter(",").unwrap(), b',');
+        assert_eq!(parse_delimiter(";").unwrap(), b';');
+        assert_eq!(parse_delimiter("\t").unwrap(), b'\t');
+        assert_eq!(parse_delimiter("\\t").unwrap(), b'\t');
+        assert!(parse_delimiter("ab").is_err());
+        assert!(parse_delimiter("，").is_err());
+    }
+
+    #[test]
+    fn test_split_prefix() {
+        assert_eq!(split_prefix("assets/juventus.csv"), "juventus");
+        assert_eq!(split_prefix("fixtures/join_*.csv"), "split");
+        assert_eq!(split_prefix("-"), "split");
+    }
+
+    #[test]
+    fn test_output_format_round_trip() {
+        for name in ["json", "yaml", "toml", "ndjson", "csv", "html"] {
+            let format: OutputFormat = name.parse().unwrap();
+            assert_eq!(format.to_string(), name);
+            assert_eq!(format.extension(), name);
+        }
+        assert_eq!(OutputFormat::Table.extension(), "txt");
+        assert_eq!(OutputFormat::Markdown.extension(), "md");
+        assert_eq!(OutputFormat::Latex.extension(), "tex");
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_parse_sort_key_and_aggregation() {
+        assert_eq!(
+            parse_sort_key("Kit Number:desc").unwrap(),
+            SortKey {
+                column: "Kit Number".into(),
+                desc: true
+            }
+        );
+        assert_eq!(
+            parse_sort_key("a:b").unwrap(),
+            SortKey {
+                column: "a:b".into(),
+                desc: false
+            }
+        );
+        let agg = parse_aggregation("avg:Kit Number").unwrap();
+        assert_eq!(agg.op, AggOp::Avg);
+        assert_eq!(agg.to_string(), "avg(Kit Number)");
+        assert!(parse_aggregation("median:Kit Number").is_err());
+        assert!(parse_aggregation("sum").is_err());
+    }
+}
@@ -1,40 +1,299 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    str::FromStr,
+};
+
 use anyhow::Result;
 use clap::Parser;
+use serde::Serialize;
+
+use crate::{
+    charset_alphabet, generate_with_min_score, passphrase_entropy, process_genpass,
+    process_genphrase, CmdExector, PasswordPolicy, Strength,
+};
 
-use crate::{process_genpass, CmdExector};
-use zxcvbn::zxcvbn;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenPassOutput {
+    Plain,
+    Json,
+    Csv,
+}
 
 #[derive(Debug, Parser)]
 pub struct GenPassOpts {
     #[arg(short, long, default_value_t = 16, help = "密码长度")]
-    pub length: u8,
+    pub length: u16,
+
+    #[arg(long, conflicts_with = "words", help = "不包含大写字母")]
+    pub no_uppercase: bool,
+
+    #[arg(long, conflicts_with = "words", help = "不包含小写字母")]
+    pub no_lowercase: bool,
+
+    #[arg(long, conflicts_with = "words", help = "不包含数字")]
+    pub no_numbers: bool,
+
+    #[arg(long, conflicts_with = "words", help = "不包含符号")]
+    pub no_symbols: bool,
+
+    #[arg(
+        long,
+        default_value_t = 1,
+        conflicts_with_all = ["no_uppercase", "words"],
+        help = "大写字母的最少个数"
+    )]
+    pub min_uppercase: u16,
+
+    #[arg(
+        long,
+        default_value_t = 1,
+        conflicts_with_all = ["no_lowercase", "words"],
+        help = "小写字母的最少个数"
+    )]
+    pub min_lowercase: u16,
+
+    #[arg(
+        long,
+        default_value_t = 1,
+        conflicts_with_all = ["no_numbers", "words"],
+        help = "数字的最少个数"
+    )]
+    pub min_numbers: u16,
+
+    #[arg(
+        long,
+        default_value_t = 1,
+        conflicts_with_all = ["no_symbols", "words"],
+        help = "符号的最少个数"
+    )]
+    pub min_symbols: u16,
 
-    #[arg(long, default_value_t = true, help = "包含大写字母")]
-    pub uppercase: bool,
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "no_uppercase",
+            "no_lowercase",
+            "no_numbers",
+            "no_symbols",
+            "min_uppercase",
+            "min_lowercase",
+            "min_numbers",
+            "min_symbols",
+            "allow_ambiguous",
+            "words",
+        ],
+        help = "字符集：hex、base58、alnum、ascii-printable，或直接给出所有可用字符"
+    )]
+    pub charset: Option<String>,
 
-    #[arg(long, default_value_t = true, help = "包含小写字母")]
-    pub lowercase: bool,
+    #[arg(
+        long,
+        default_value = "",
+        help = "排除的字符，例如目标系统不接受的引号；口令模式下不使用包含这些字符的单词"
+    )]
+    pub exclude: String,
 
-    #[arg(long, default_value_t = true, help = "包含数字")]
-    pub number: bool,
+    #[arg(long, conflicts_with = "words", help = "允许易混淆的字符 0、O、I、l")]
+    pub allow_ambiguous: bool,
 
-    #[arg(long, default_value_t = true, help = "包含符号")]
-    pub symbol: bool,
+    #[arg(
+        long,
+        conflicts_with = "length",
+        help = "生成由 N 个随机单词组成的口令，而不是随机字符"
+    )]
+    pub words: Option<u16>,
+
+    #[arg(
+        long,
+        default_value = "-",
+        requires = "words",
+        help = "口令中单词之间的分隔符"
+    )]
+    pub separator: String,
+
+    #[arg(long, requires = "words", help = "口令中每个单词首字母大写")]
+    pub capitalize: bool,
+
+    #[arg(
+        long,
+        requires = "words",
+        help = "在口令中随机一个单词后加一位随机数字"
+    )]
+    pub digit: bool,
+
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=4), help = "zxcvbn 最低强度 (0-4)，不满足时重新生成")]
+    pub min_score: u8,
+
+    #[arg(long, help = "输出 zxcvbn 估算的破解时间、警告和建议")]
+    pub explain: bool,
+
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), help = "生成的密码数量，指定时 json 输出为数组")]
+    pub count: Option<u32>,
+
+    #[arg(long, value_parser = parse_genpass_output, default_value = "plain", help = "输出格式：plain、json 或 csv")]
+    pub output: GenPassOutput,
+}
+
+/// One generated password as written by the json and csv outputs
+#[derive(Debug, Serialize)]
+struct Generated {
+    password: String,
+    length: usize,
+    entropy: f64,
+    score: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explain: Option<Strength>,
 }
 
 impl CmdExector for GenPassOpts {
     async fn execute(self) -> Result<()> {
-        let password = process_genpass(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.number,
-            self.symbol,
-        )?;
-        println!("{}", password);
-
-        let estimate = zxcvbn(&password, &[])?;
-        eprintln!("Estimated strength: {}", estimate.score());
+        let entropy = self.entropy();
+        let generated = (0..self.count.unwrap_or(1))
+            .map(|_| {
+                let (password, strength) = self.generate()?;
+                Ok(Generated {
+                    length: password.chars().count(),
+                    password,
+                    entropy,
+                    score: strength.score,
+                    explain: self.explain.then_some(strength),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        match self.output {
+            GenPassOutput::Json => {
+                let json = match (self.count, generated.as_slice()) {
+                    (None, [one]) => serde_json::to_string_pretty(one)?,
+                    _ => serde_json::to_string_pretty(&generated)?,
+                };
+                println!("{}", json);
+            }
+            GenPassOutput::Csv => {
+                let mut writer = csv::Writer::from_writer(io::stdout());
+                writer.write_record(["password", "length", "entropy", "score"])?;
+                for g in &generated {
+                    writer.write_record([
+                        g.password.clone(),
+                        g.length.to_string(),
+                        format!("{:.2}", g.entropy),
+                        g.score.to_string(),
+                    ])?;
+                }
+                writer.flush()?;
+            }
+            GenPassOutput::Plain => {
+                for g in &generated {
+                    println!("{}", g.password);
+                }
+                // details only make sense for a single password
+                if let [g] = generated.as_slice() {
+                    eprintln!("Entropy: {:.2} bits", g.entropy);
+                    eprintln!("Estimated strength: {}", g.score);
+                    if let Some(strength) = &g.explain {
+                        print_explain(strength);
+                    }
+                }
+            }
+        }
         Ok(())
     }
 }
+
+impl GenPassOpts {
+    fn generate(&self) -> Result<(String, Strength)> {
+        generate_with_min_score(self.min_score, || match self.words {
+            Some(words) => process_genphrase(
+                words,
+                &self.separator,
+                self.capitalize,
+                self.digit,
+                &self.exclude,
+            ),
+            None => process_genpass(&self.policy()),
+        })
+    }
+
+    fn entropy(&self) -> f64 {
+        match self.words {
+            Some(words) => passphrase_entropy(words, self.capitalize, self.digit, &self.exclude),
+            None => self.policy().entropy(),
+        }
+    }
+
+    fn policy(&self) -> PasswordPolicy {
+        let class = |disabled: bool, min: u16| (!disabled).then_some(min);
+        PasswordPolicy {
+            length: self.length,
+            upper: class(self.no_uppercase, self.min_uppercase),
+            lower: class(self.no_lowercase, self.min_lowercase),
+            number: class(self.no_numbers, self.min_numbers),
+            symbol: class(self.no_symbols, self.min_symbols),
+            charset: self.charset.as_deref().map(charset_alphabet),
+            exclude: self.exclude.clone(),
+            allow_ambiguous: self.allow_ambiguous,
+        }
+    }
+}
+
+fn print_explain(strength: &Strength) {
+    let times = &strength.crack_times;
+    eprintln!("Guesses: 10^{:.2}", strength.guesses_log10);
+    eprintln!("Crack time:");
+    eprintln!(
+        "  online, throttled (100/hour): {}",
+        times.online_throttling_100_per_hour
+    );
+    eprintln!(
+        "  online, unthrottled (10/s): {}",
+        times.online_no_throttling_10_per_second
+    );
+    eprintln!(
+        "  offline, slow hash (1e4/s): {}",
+        times.offline_slow_hashing_1e4_per_second
+    );
+    eprintln!(
+        "  offline, fast hash (1e10/s): {}",
+        times.offline_fast_hashing_1e10_per_second
+    );
+    if let Some(warning) = &strength.warning {
+        eprintln!("Warning: {}", warning);
+    }
+    for suggestion in &strength.suggestions {
+        eprintln!("Suggestion: {}", suggestion);
+    }
+}
+
+fn parse_genpass_output(output: &str) -> Result<GenPassOutput, anyhow::Error> {
+    output.parse()
+}
+
+impl From<GenPassOutput> for &'static str {
+    fn from(output: GenPassOutput) -> Self {
+        match output {
+            GenPassOutput::Plain => "plain",
+            GenPassOutput::Json => "json",
+            GenPassOutput::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for GenPassOutput {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(GenPassOutput::Plain),
+            "json" => Ok(GenPassOutput::Json),
+            "csv" => Ok(GenPassOutput::Csv),
+            _ => anyhow::bail!("Invalid output: {}", s),
+        }
+    }
+}
+
+impl Display for GenPassOutput {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
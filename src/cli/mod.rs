@@ -26,6 +26,8 @@ pub struct Opts {
     pub cmd: SubCommand,
 }
 
+// parsed once at startup, boxing the larger options isn't worth it
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 #[enum_dispatch(CmdExector)]
 pub enum SubCommand {
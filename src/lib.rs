@@ -5,17 +5,42 @@ mod utils;
 use anyhow::Result;
 pub use cli::*;
 use enum_dispatch::enum_dispatch;
+pub use process::charset_alphabet;
+pub use process::generate_with_min_score;
+pub use process::nest_records;
+pub use process::passphrase_entropy;
 pub use process::process_csv;
+pub use process::process_csv_diff;
+pub use process::process_csv_group;
+pub use process::process_csv_join;
+pub use process::process_csv_merge;
+pub use process::process_csv_query;
+pub use process::process_csv_render;
+pub use process::process_csv_render_rows;
+pub use process::process_csv_show;
+pub use process::process_csv_split;
+pub use process::process_csv_stats;
+pub use process::process_csv_to_sqlite;
 pub use process::process_decode;
 pub use process::process_encode;
 pub use process::process_genpass;
+pub use process::process_genphrase;
 pub use process::process_http_serve;
 pub use process::process_text_decrypt;
 pub use process::process_text_encrypt;
 pub use process::process_text_generate;
 pub use process::process_text_sign;
 pub use process::process_text_verify;
+pub use process::read_records;
+pub use process::transform_records;
+pub use process::write_records;
+pub use process::PasswordPolicy;
+pub use process::RejectSink;
+pub use process::SplitBy;
+pub use process::Strength;
 pub use utils::get_reader;
+pub use utils::get_writer;
+pub use utils::print_paged;
 
 #[allow(async_fn_in_trait)]
 #[enum_dispatch]
@@ -1,37 +1,359 @@
-use std::fs;
+use std::{cell::Cell, rc::Rc};
 
 use anyhow::Result;
-use csv::Reader;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-
-use crate::cli::OutputFormat;
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct Player {
-    name: String,
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
-}
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        ret.push(json_value);
-    }
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
+use crate::cli::{
+    CsvReaderOpts, CsvTransformOpts, CsvWriterOpts, InputFormat, OnError, OutputFormat,
+};
+
+use super::{
+    csv_nest::nest_records,
+    csv_reader::read_records,
+    csv_transform::transform_records,
+    csv_validate::{RowRef, RowValidator},
+    csv_writer::write_records,
+};
+
+/// Convert the input, rows are checked against `json_schema` as they are
+/// written (after `--nest`) and every violation is reported on stderr with
+/// the source line of the row, or its index in the output once `--sort-by`
+/// reorders rows or the input has no lines. The output is still written in
+/// full, but the conversion fails if any row is invalid.
+#[allow(clippy::too_many_arguments)]
+pub fn process_csv(
+    input: &str,
+    input_format: InputFormat,
+    output: String,
+    format: OutputFormat,
+    opts: &CsvReaderOpts,
+    transform: &CsvTransformOpts,
+    writer_opts: &CsvWriterOpts,
+    json_schema: Option<&str>,
+) -> Result<()> {
+    let validator = json_schema.map(RowValidator::load).transpose()?;
+    let records = read_records(input, input_format, opts, &writer_opts.toml_key)?;
+    let summary = records.summary.clone();
+    let records = transform_records(records, transform)?;
+    let mut records = if writer_opts.nest {
+        nest_records(records)?
+    } else {
+        records
     };
-    fs::write(output, content)?;
+    let written = Rc::new(Cell::new(0u64));
+    let invalid = Rc::new(Cell::new(0u64));
+    let (counter, invalid_counter) = (written.clone(), invalid.clone());
+    // every other step is lazy, so the last row read is the one being written
+    let by_line = transform.sort_by.is_empty();
+    let source = summary.clone();
+    records.rows = Box::new(records.rows.inspect(move |row| {
+        let Ok(row) = row else { return };
+        counter.set(counter.get() + 1);
+        if let Some(validator) = &validator {
+            let row_ref = match source.line.get() {
+                line if by_line && line > 0 => RowRef::Line(line),
+                _ => RowRef::Output(counter.get()),
+            };
+            let violations = validator.check(row_ref, row);
+            for violation in &violations {
+                eprintln!("{}", violation);
+            }
+            if !violations.is_empty() {
+                invalid_counter.set(invalid_counter.get() + 1);
+            }
+        }
+    }));
+    let writer_opts = CsvWriterOpts {
+        nest: false,
+        ..writer_opts.clone()
+    };
+    write_records(records, &output, format, &writer_opts)?;
+    if opts.on_error != OnError::Strict {
+        eprintln!(
+            "Rows read: {}, written: {}, rejected: {}",
+            summary.read.get(),
+            written.get(),
+            summary.rejected.get()
+        );
+    }
+    if invalid.get() > 0 {
+        anyhow::bail!(
+            "{} of {} rows failed JSON Schema validation",
+            invalid.get(),
+            written.get()
+        );
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempPath;
+    use serde_json::Value;
+    use std::fs;
+
+    fn convert_to(input: &str, format: OutputFormat, opts: &CsvReaderOpts) -> Result<String> {
+        let output = TempPath::new(&format!(".{}", format.extension()));
+        process_csv(
+            input,
+            InputFormat::Csv,
+            output.to_string(),
+            format,
+            opts,
+            &Default::default(),
+            &Default::default(),
+            None,
+        )?;
+        let content = fs::read_to_string(&output)?;
+        Ok(content)
+    }
+
+    fn convert(input: &str, opts: &CsvReaderOpts) -> Result<Vec<Value>> {
+        let content = convert_to(input, OutputFormat::Json, opts)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    #[test]
+    fn test_process_csv_with_delimiter() -> Result<()> {
+        let opts = CsvReaderOpts {
+            delimiter: b';',
+            ..Default::default()
+        };
+        let ret = convert("fixtures/players.csv", &opts)?;
+        assert_eq!(ret.len(), 3);
+        assert_eq!(ret[0]["Name"], "Wojciech Szczesny");
+        assert_eq!(ret[0]["Kit Number"], "1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_without_header() -> Result<()> {
+        let opts = CsvReaderOpts {
+            delimiter: b'\t',
+            header: false,
+            ..Default::default()
+        };
+        let ret = convert("fixtures/players.tsv", &opts)?;
+        assert_eq!(ret.len(), 3);
+        assert_eq!(ret[0]["col1"], "Wojciech Szczesny");
+        assert_eq!(ret[2]["col5"], "77");
+
+        let opts = CsvReaderOpts {
+            columns: Some(vec!["name".into(), "position".into(), "dob".into()]),
+            ..opts
+        };
+        assert!(convert("fixtures/players.tsv", &opts).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_to_other_formats() -> Result<()> {
+        let opts = CsvReaderOpts::default();
+
+        let content = convert_to("assets/juventus.csv", OutputFormat::Toml, &opts)?;
+        let table: toml::Table = content.parse()?;
+        assert_eq!(table["records"].as_array().unwrap().len(), 27);
+
+        let content = convert_to("assets/juventus.csv", OutputFormat::Ndjson, &opts)?;
+        assert_eq!(content.lines().count(), 27);
+        let first: Value = serde_json::from_str(content.lines().next().unwrap())?;
+        assert_eq!(first["Name"], "Wojciech Szczesny");
+
+        let content = convert_to("assets/juventus.csv", OutputFormat::Csv, &opts)?;
+        assert_eq!(content, fs::read_to_string("assets/juventus.csv")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_json_to_csv() -> Result<()> {
+        let output = TempPath::new(".csv");
+        process_csv(
+            "fixtures/users.json",
+            InputFormat::Json,
+            output.to_string(),
+            OutputFormat::Csv,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )?;
+        let content = fs::read_to_string(&output)?;
+        let mut lines = content.lines();
+        assert_eq!(
+            lines.next(),
+            Some("id,name,address.city,address.geo.lat,tags,active,email")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(r#"1,Alice,Turin,45.07,"[""admin"",""dev""]",true,"#)
+        );
+        assert_eq!(lines.next(), Some("2,Bob,Milan,,[],,bob@example.com"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_with_types() -> Result<()> {
+        let opts = CsvReaderOpts {
+            infer_types: true,
+            ..Default::default()
+        };
+        let ret = convert("assets/juventus.csv", &opts)?;
+        assert_eq!(ret[0]["Kit Number"], 1);
+        assert_eq!(ret[0]["DOB"], "Apr 18, 1990 (29)");
+
+        let content = convert_to("assets/juventus.csv", OutputFormat::Toml, &opts)?;
+        assert!(content.contains("\"Kit Number\" = 1\n"));
+
+        let opts = CsvReaderOpts {
+            schema: Some("fixtures/players_schema.yml".into()),
+            ..Default::default()
+        };
+        let ret = convert("assets/juventus.csv", &opts)?;
+        assert_eq!(ret[0]["Kit Number"], 1);
+        assert_eq!(ret[0]["Nationality"], "Poland");
+
+        let opts = CsvReaderOpts {
+            schema: Some("fixtures/players_bad_schema.yml".into()),
+            ..Default::default()
+        };
+        let err = convert("assets/juventus.csv", &opts).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Invalid row at line 2 (byte 41): invalid integer in column 4 (Nationality): "Poland""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_on_error() -> Result<()> {
+        let input = "fixtures/players_malformed.csv";
+        let err = convert(input, &CsvReaderOpts::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid row at line 3 (byte 56): expected 3 fields, found 2"
+        );
+
+        let opts = CsvReaderOpts {
+            on_error: OnError::Skip,
+            ..Default::default()
+        };
+        let ret = convert(input, &opts)?;
+        let names: Vec<_> = ret.iter().map(|r| r["Name"].as_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            ["Wojciech Szczesny", "Gianluigi Buffon", "Leonardo Bonucci"]
+        );
+
+        let reject_file = TempPath::new(".csv");
+        let opts = CsvReaderOpts {
+            on_error: OnError::Report,
+            reject_file: reject_file.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(convert(input, &opts)?.len(), 3);
+        let content = fs::read(&reject_file)?;
+        // the original bytes are kept, even when they aren't valid UTF-8
+        assert!(content.windows(7).any(|w| w == b"Goalk\xe9e"));
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(content.as_slice());
+        let rows = reader
+            .byte_records()
+            .map(|row| {
+                Ok(row?
+                    .iter()
+                    .map(|f| String::from_utf8_lossy(f).to_string())
+                    .collect())
+            })
+            .collect::<Result<Vec<Vec<String>>>>()?;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            [
+                "fixtures/players_malformed.csv",
+                "3",
+                "56",
+                "",
+                "expected 3 fields, found 2",
+                "Mattia Perin",
+                "Goalkeeper"
+            ]
+        );
+        assert_eq!(
+            rows[1][1..5],
+            ["5", "111", "", "invalid UTF-8 in column 2 (Position)"]
+        );
+        assert_eq!(rows[2][4], "expected 3 fields, found 4");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_with_encoding() -> Result<()> {
+        let ret = convert("fixtures/players_gbk.csv", &Default::default())?;
+        assert_eq!(ret.len(), 2);
+        assert_eq!(ret[1]["姓名"], "布冯");
+
+        let opts = CsvReaderOpts {
+            delimiter: b'\t',
+            ..Default::default()
+        };
+        let ret = convert("fixtures/players_utf16.csv", &opts)?;
+        assert_eq!(ret[0]["姓名"], "什琴斯尼");
+        assert_eq!(ret[1]["号码"], "77");
+
+        // offsets into transcoded input don't match the source file
+        let input = TempPath::new(".csv");
+        let (gbk, _, _) = encoding_rs::GBK.encode("姓名,位置\n布冯,门将\n基耶利尼\n");
+        fs::write(&input, gbk)?;
+        let err = convert(&input, &Default::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid row at line 3 (byte 28 of the decoded UTF-8): expected 2 fields, found 1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_auto_encoding_with_stray_byte() -> Result<()> {
+        let reject_file = TempPath::new(".csv");
+        let opts = CsvReaderOpts {
+            on_error: OnError::Report,
+            reject_file: reject_file.to_string(),
+            ..Default::default()
+        };
+        let ret = convert("fixtures/players_stray_byte.csv", &opts)?;
+        let content = fs::read(&reject_file)?;
+        let names: Vec<_> = ret.iter().map(|r| r["姓名"].as_str().unwrap()).collect();
+        assert_eq!(names, ["什琴斯尼", "布冯", "博努奇"]);
+        assert!(String::from_utf8_lossy(&content).contains("invalid UTF-8 in column 2 (位置)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_with_json_schema() -> Result<()> {
+        let output = TempPath::new(".json");
+        let opts = CsvReaderOpts {
+            infer_types: true,
+            ..Default::default()
+        };
+        let convert = |input: &str| {
+            process_csv(
+                input,
+                InputFormat::Csv,
+                output.to_string(),
+                OutputFormat::Json,
+                &opts,
+                &Default::default(),
+                &Default::default(),
+                Some("fixtures/players.schema.json"),
+            )
+        };
+        convert("assets/juventus.csv")?;
+        let err = convert("fixtures/players_invalid.csv").unwrap_err();
+        assert_eq!(err.to_string(), "2 of 3 rows failed JSON Schema validation");
+        // invalid rows are still written
+        let rows: Vec<Value> = serde_json::from_str(&fs::read_to_string(&output)?)?;
+        assert_eq!(rows.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::cli::{CsvReaderOpts, InputFormat};
+
+use super::{csv_reader::read_records, csv_transform::row_key, csv_writer::value_to_cell};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Default, Serialize)]
+pub struct CsvDiff {
+    pub columns_added: Vec<String>,
+    pub columns_removed: Vec<String>,
+    pub added: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub changed: Vec<RowChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowChange {
+    pub key: Map<String, Value>,
+    pub changes: Vec<CellChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CellChange {
+    pub column: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Compare two versions of a CSV by the key columns. The old file is loaded in
+/// memory, the new one is streamed.
+pub fn process_csv_diff(
+    old: &str,
+    new: &str,
+    opts: &CsvReaderOpts,
+    key: &[String],
+) -> Result<CsvDiff> {
+    let old = read_records(old, InputFormat::Csv, opts, "")?;
+    let new = read_records(new, InputFormat::Csv, opts, "")?;
+    for (side, headers) in [("old", &old.headers), ("new", &new.headers)] {
+        if let Some(name) = key.iter().find(|name| !headers.contains(name)) {
+            anyhow::bail!("Key column not found in {} file: {}", side, name);
+        }
+    }
+
+    let mut diff = CsvDiff {
+        columns_added: new
+            .headers
+            .iter()
+            .filter(|h| !old.headers.contains(h))
+            .cloned()
+            .collect(),
+        columns_removed: old
+            .headers
+            .iter()
+            .filter(|h| !new.headers.contains(h))
+            .cloned()
+            .collect(),
+        ..Default::default()
+    };
+    // added/removed columns are reported once rather than on every row
+    let columns: Vec<String> = old
+        .headers
+        .iter()
+        .filter(|h| new.headers.contains(h) && !key.contains(h))
+        .cloned()
+        .collect();
+
+    let mut old_rows: IndexMap<Vec<String>, Value> = IndexMap::new();
+    for row in old.rows {
+        let row = row?;
+        let k = row_key(&row, key);
+        if old_rows.contains_key(&k) {
+            anyhow::bail!(
+                "Duplicate key in old file: {}",
+                key_text(&key_of(&row, key), key)
+            );
+        }
+        old_rows.insert(k, row);
+    }
+    let mut seen = HashSet::new();
+    for row in new.rows {
+        let row = row?;
+        let k = row_key(&row, key);
+        if !seen.insert(k.clone()) {
+            anyhow::bail!(
+                "Duplicate key in new file: {}",
+                key_text(&key_of(&row, key), key)
+            );
+        }
+        match old_rows.shift_remove(&k) {
+            Some(old_row) => {
+                let changes: Vec<CellChange> = columns
+                    .iter()
+                    .filter_map(|c| {
+                        let old = old_row.get(c).cloned().unwrap_or(Value::Null);
+                        let new = row.get(c).cloned().unwrap_or(Value::Null);
+                        (old != new).then(|| CellChange {
+                            column: c.clone(),
+                            old,
+                            new,
+                        })
+                    })
+                    .collect();
+                if !changes.is_empty() {
+                    diff.changed.push(RowChange {
+                        key: key_of(&row, key),
+                        changes,
+                    });
+                }
+            }
+            None => diff.added.push(row),
+        }
+    }
+    diff.removed = old_rows.into_values().collect();
+    Ok(diff)
+}
+
+impl CsvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.columns_added.is_empty()
+            && self.columns_removed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// A human-readable report, `-` for removed, `+` for added, `~` for changed rows
+    pub fn to_text(&self, key: &[String], color: bool) -> String {
+        let paint = |code: &str, s: String| {
+            if color {
+                format!("{}{}{}", code, s, RESET)
+            } else {
+                s
+            }
+        };
+        let mut out = String::new();
+        for c in &self.columns_removed {
+            out.push_str(&paint(RED, format!("- column {}", c)));
+            out.push('\n');
+        }
+        for c in &self.columns_added {
+            out.push_str(&paint(GREEN, format!("+ column {}", c)));
+            out.push('\n');
+        }
+        for row in &self.removed {
+            out.push_str(&paint(
+                RED,
+                format!("- {}", key_text(&key_of(row, key), key)),
+            ));
+            out.push('\n');
+        }
+        for row in &self.added {
+            out.push_str(&paint(
+                GREEN,
+                format!("+ {}", key_text(&key_of(row, key), key)),
+            ));
+            out.push('\n');
+        }
+        for row in &self.changed {
+            out.push_str(&paint(YELLOW, format!("~ {}", key_text(&row.key, key))));
+            out.push('\n');
+            for change in &row.changes {
+                out.push_str(&format!(
+                    "    {}: {} -> {}\n",
+                    change.column,
+                    paint(RED, value_to_cell(&change.old)),
+                    paint(GREEN, value_to_cell(&change.new)),
+                ));
+            }
+        }
+        out.push_str(&format!(
+            "{} added, {} removed, {} changed\n",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        ));
+        out
+    }
+}
+
+fn key_of(row: &Value, key: &[String]) -> Map<String, Value> {
+    key.iter()
+        .map(|k| (k.clone(), row.get(k).cloned().unwrap_or(Value::Null)))
+        .collect()
+}
+
+fn key_text(values: &Map<String, Value>, key: &[String]) -> String {
+    key.iter()
+        .map(|k| format!("{}={}", k, value_to_cell(&values[k])))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_process_csv_diff() -> Result<()> {
+        let diff = process_csv_diff(
+            "fixtures/join_right.csv",
+            "fixtures/diff_new.csv",
+            &Default::default(),
+            &["id".to_string(), "goals".to_string()],
+        )?;
+        assert!(!diff.is_empty());
+        assert_eq!(diff.columns_added, vec!["assists"]);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0]["id"], "4");
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0]["name"], "Federico Chiesa");
+        assert_eq!(diff.changed.len(), 1);
+        let change = &diff.changed[0].changes;
+        assert_eq!(change[0].column, "name");
+        assert_eq!(change[0].new, json!("Dybala"));
+
+        let text = diff.to_text(&["id".to_string(), "goals".to_string()], false);
+        assert!(text.contains("- id=4, goals=9\n"));
+        assert!(text.contains("+ id=5, goals=3\n"));
+        assert!(text.ends_with("1 added, 1 removed, 1 changed\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_diff_same_file() -> Result<()> {
+        let diff = process_csv_diff(
+            "assets/juventus.csv",
+            "assets/juventus.csv",
+            &Default::default(),
+            &["Name".to_string()],
+        )?;
+        assert!(diff.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+use crate::cli::CsvEncoding;
+
+/// Bytes looked at to guess the encoding of input without a BOM
+const SNIFF_LEN: u64 = 64 * 1024;
+
+/// Fewer invalid sequences than this are stray bytes, not another encoding
+const MIN_INVALID: usize = 4;
+
+/// Transcode the input to UTF-8, a BOM always wins over the given encoding
+/// and is stripped. UTF-8 is passed through unchanged so invalid bytes still
+/// reach the CSV reader and are handled by `--on-error`.
+///
+/// Also returns whether the decoded bytes differ from the source, in which
+/// case byte offsets into them don't match the input file.
+pub fn decode_reader(
+    mut reader: Box<dyn Read>,
+    encoding: CsvEncoding,
+) -> Result<(Box<dyn Read>, bool)> {
+    let sniff_len = match encoding {
+        CsvEncoding::Auto => SNIFF_LEN,
+        // just enough for a BOM
+        CsvEncoding::Label(_) => 3,
+    };
+    let mut prefix = Vec::new();
+    reader.by_ref().take(sniff_len).read_to_end(&mut prefix)?;
+    let encoding = match encoding {
+        CsvEncoding::Label(encoding) => encoding,
+        CsvEncoding::Auto => detect(&prefix),
+    };
+    let transcoded = encoding != UTF_8 || Encoding::for_bom(&prefix).is_some();
+    let reader = Box::new(Cursor::new(prefix).chain(reader));
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding).filter(|e| *e != UTF_8))
+        .utf8_passthru(true)
+        .strip_bom(true)
+        .bom_override(true)
+        .build(reader);
+    Ok((Box::new(decoder), transcoded))
+}
+
+/// Guess the encoding from the first bytes of the input: the BOM if there is
+/// one, UTF-8 unless the bytes are clearly something else, otherwise a
+/// statistical guess. A few stray bytes in UTF-8 text are left to `--on-error`
+/// rather than turning the whole file into mojibake.
+fn detect(prefix: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(prefix) {
+        return encoding;
+    }
+    let (valid, invalid) = utf8_sequences(prefix);
+    if invalid <= valid || invalid < MIN_INVALID {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(prefix, (prefix.len() as u64) < SNIFF_LEN);
+    detector.guess(None, true)
+}
+
+/// Count the valid non-ASCII characters and the invalid sequences, a
+/// character cut at the end of the prefix counts as neither
+fn utf8_sequences(mut bytes: &[u8]) -> (usize, usize) {
+    let non_ascii = |s: &str| s.chars().filter(|c| !c.is_ascii()).count();
+    let (mut valid, mut invalid) = (0, 0);
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => return (valid + non_ascii(s), invalid),
+            Err(e) => {
+                let (ok, rest) = bytes.split_at(e.valid_up_to());
+                valid += non_ascii(std::str::from_utf8(ok).expect("valid up to here"));
+                let Some(len) = e.error_len() else {
+                    return (valid, invalid);
+                };
+                invalid += 1;
+                bytes = &rest[len..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, UTF_16LE};
+
+    fn decode(bytes: &[u8], encoding: CsvEncoding) -> Result<Vec<u8>> {
+        let mut ret = Vec::new();
+        let (mut reader, _) = decode_reader(Box::new(Cursor::new(bytes.to_vec())), encoding)?;
+        reader.read_to_end(&mut ret)?;
+        Ok(ret)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(b"\xEF\xBB\xBFName"), UTF_8);
+        assert_eq!(detect(b"\xFF\xFEN\x00"), UTF_16LE);
+        assert_eq!(detect("姓名".as_bytes()), UTF_8);
+        // "姓名" cut in the middle of the second character
+        assert_eq!(detect(&"姓名".as_bytes()[..4]), UTF_8);
+        let (gbk, _, _) = GBK.encode("姓名,位置\n布冯,门将\n基耶利尼,后卫\n");
+        assert_eq!(detect(&gbk), GBK);
+
+        // mostly valid UTF-8 with a stray byte stays UTF-8
+        let mut bytes = "姓名,位置\n布冯,门将\n".as_bytes().to_vec();
+        bytes.extend(b"\xff,\xe9\n");
+        assert_eq!(detect(&bytes), UTF_8);
+        assert_eq!(detect(b"Name\nGoalk\xe9eper\n"), UTF_8);
+    }
+
+    #[test]
+    fn test_utf8_sequences() {
+        assert_eq!(utf8_sequences("a布冯b".as_bytes()), (2, 0));
+        assert_eq!(utf8_sequences(b"a\xffb\xe9"), (0, 1));
+        assert_eq!(utf8_sequences(b"\xff\xfe\xe5\xb8"), (0, 2));
+    }
+
+    #[test]
+    fn test_decode_reader() -> Result<()> {
+        let text = "姓名,位置\n布冯,门将\n";
+        assert_eq!(decode(text.as_bytes(), CsvEncoding::Auto)?, text.as_bytes());
+        assert_eq!(
+            decode(b"\xEF\xBB\xBFName\n\xFF", CsvEncoding::Auto)?,
+            b"Name\n\xFF"
+        );
+
+        let (gbk, _, _) = GBK.encode(text);
+        assert_eq!(decode(&gbk, CsvEncoding::Label(GBK))?, text.as_bytes());
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(text.encode_utf16().flat_map(|c| c.to_le_bytes()));
+        assert_eq!(decode(&utf16, CsvEncoding::Auto)?, text.as_bytes());
+        // the BOM wins over an explicit encoding
+        assert_eq!(decode(&utf16, CsvEncoding::Label(GBK))?, text.as_bytes());
+
+        let transcoded = |bytes: &[u8], encoding| -> Result<bool> {
+            Ok(decode_reader(Box::new(Cursor::new(bytes.to_vec())), encoding)?.1)
+        };
+        assert!(!transcoded(text.as_bytes(), CsvEncoding::Auto)?);
+        assert!(transcoded(b"\xEF\xBB\xBFName", CsvEncoding::Auto)?);
+        assert!(transcoded(&gbk, CsvEncoding::Auto)?);
+        assert!(transcoded(&utf16, CsvEncoding::Label(UTF_8))?);
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+
+use anyhow::Result;
+use serde_json::Value;
+
+/// A parsed `--where` expression, e.g. `Position == "Forward" && Kit Number > 9`.
+///
+/// Bare words are column names (spaces allowed), use backticks for names that
+/// clash with literals. Strings are quoted with `"` or `'`. Operators are
+/// `== != > >= < <= && || !` and parentheses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Column(String),
+    Literal(Value),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Compare(Box<Filter>, CompareOp, Box<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Ident(String),
+    Str(String),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let filter = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            anyhow::bail!("Unexpected token in filter: {:?}", token);
+        }
+        Ok(filter)
+    }
+
+    /// Every column the expression refers to
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Filter::Column(name) => vec![name.as_str()],
+            Filter::Literal(_) => Vec::new(),
+            Filter::Not(f) => f.columns(),
+            Filter::And(l, r) | Filter::Or(l, r) | Filter::Compare(l, _, r) => {
+                let mut columns = l.columns();
+                columns.extend(r.columns());
+                columns
+            }
+        }
+    }
+
+    pub fn matches(&self, row: &Value) -> bool {
+        is_truthy(&self.eval(row))
+    }
+
+    fn eval(&self, row: &Value) -> Value {
+        match self {
+            Filter::Column(name) => row.get(name).cloned().unwrap_or(Value::Null),
+            Filter::Literal(v) => v.clone(),
+            Filter::Not(f) => Value::Bool(!f.matches(row)),
+            Filter::And(l, r) => Value::Bool(l.matches(row) && r.matches(row)),
+            Filter::Or(l, r) => Value::Bool(l.matches(row) || r.matches(row)),
+            Filter::Compare(l, op, r) => {
+                let ordering = compare_values(&l.eval(row), &r.eval(row));
+                let ret = match op {
+                    CompareOp::Eq => ordering == Some(Ordering::Equal),
+                    CompareOp::Ne => ordering != Some(Ordering::Equal),
+                    CompareOp::Gt => ordering == Some(Ordering::Greater),
+                    CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    CompareOp::Lt => ordering == Some(Ordering::Less),
+                    CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                };
+                Value::Bool(ret)
+            }
+        }
+    }
+}
+
+/// Compare two cells, numeric strings compare as numbers.
+/// Returns `None` when the values are not comparable.
+pub fn compare_values(l: &Value, r: &Value) -> Option<Ordering> {
+    match (l, r) {
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => {
+            match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.partial_cmp(&b),
+                _ => Some(a.cmp(b)),
+            }
+        }
+        (a, b) => match (as_f64(a), as_f64(b)) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => None,
+        },
+    }
+}
+
+fn as_f64(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn is_truthy(v: &Value) -> bool {
+    match v {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        _ => true,
+    }
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<Filter> {
+        let mut left = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            left = Filter::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter> {
+        let mut left = self.parse_not()?;
+        while self.eat(&Token::And) {
+            left = Filter::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Filter> {
+        if self.eat(&Token::Not) {
+            return Ok(Filter::Not(Box::new(self.parse_not()?)));
+        }
+        let left = self.parse_primary()?;
+        if let Some(Token::Op(op)) = self.tokens.get(self.pos).cloned() {
+            self.pos += 1;
+            let right = self.parse_primary()?;
+            return Ok(Filter::Compare(Box::new(left), op, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn parse_primary(&mut self) -> Result<Filter> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of filter"))?;
+        self.pos += 1;
+        match token {
+            Token::LParen => {
+                let inner = self.parse_or()?;
+                if !self.eat(&Token::RParen) {
+                    anyhow::bail!("Missing closing parenthesis in filter");
+                }
+                Ok(inner)
+            }
+            Token::Str(s) => Ok(Filter::Literal(Value::String(s))),
+            Token::Ident(s) => Ok(Filter::Column(s)),
+            Token::Word(w) => Ok(match w.as_str() {
+                "true" => Filter::Literal(Value::Bool(true)),
+                "false" => Filter::Literal(Value::Bool(false)),
+                "null" => Filter::Literal(Value::Null),
+                _ => match w.parse::<f64>() {
+                    Ok(n) => Filter::Literal(serde_json::json!(n)),
+                    Err(_) => Filter::Column(w),
+                },
+            }),
+            t => anyhow::bail!("Unexpected token in filter: {:?}", t),
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('=', Some('=')) => (Token::Op(CompareOp::Eq), 2),
+            ('!', Some('=')) => (Token::Op(CompareOp::Ne), 2),
+            ('>', Some('=')) => (Token::Op(CompareOp::Ge), 2),
+            ('<', Some('=')) => (Token::Op(CompareOp::Le), 2),
+            ('>', _) => (Token::Op(CompareOp::Gt), 1),
+            ('<', _) => (Token::Op(CompareOp::Lt), 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('!', _) => (Token::Not, 1),
+            ('"' | '\'' | '`', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&ch| ch == c)
+                    .ok_or_else(|| anyhow::anyhow!("Unterminated {} in filter", c))?;
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                let token = if c == '`' {
+                    Token::Ident(text)
+                } else {
+                    Token::Str(text)
+                };
+                (token, end + 2)
+            }
+            _ => {
+                // a bare word runs up to the next operator, so column names may contain spaces
+                let end = chars[i..]
+                    .iter()
+                    .position(|ch| "()=!<>&|\"'`".contains(*ch))
+                    .unwrap_or(chars.len() - i);
+                let word: String = chars[i..i + end].iter().collect();
+                if word.trim().is_empty() {
+                    anyhow::bail!("Unexpected character in filter: {}", c);
+                }
+                (Token::Word(word.trim().to_string()), end)
+            }
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_parse() -> Result<()> {
+        let filter = Filter::parse(r#"Position == "Forward" && Kit Number > 9"#)?;
+        assert_eq!(
+            filter,
+            Filter::And(
+                Box::new(Filter::Compare(
+                    Box::new(Filter::Column("Position".into())),
+                    CompareOp::Eq,
+                    Box::new(Filter::Literal(json!("Forward"))),
+                )),
+                Box::new(Filter::Compare(
+                    Box::new(Filter::Column("Kit Number".into())),
+                    CompareOp::Gt,
+                    Box::new(Filter::Literal(json!(9.0))),
+                )),
+            )
+        );
+        assert!(Filter::parse("a == ").is_err());
+        assert!(Filter::parse("(a == 1").is_err());
+        assert!(Filter::parse("a == 'x").is_err());
+
+        let filter = Filter::parse("!(a == 1) || `b c` > d && true")?;
+        assert_eq!(filter.columns(), ["a", "b c", "d"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_matches() -> Result<()> {
+        let row = json!({"Position": "Forward", "Kit Number": "10", "true": "x"});
+        assert!(Filter::parse(r#"Position == "Forward" && Kit Number > 9"#)?.matches(&row));
+        assert!(!Filter::parse("Kit Number >= 11 || Position != 'Forward'")?.matches(&row));
+        assert!(Filter::parse("!(Kit Number < 10)")?.matches(&row));
+        assert!(Filter::parse("Missing == null")?.matches(&row));
+        assert!(Filter::parse("`true` == 'x'")?.matches(&row));
+        // strings and numbers which can't be compared never match
+        assert!(!Filter::parse("Position > 1")?.matches(&row));
+        Ok(())
+    }
+}
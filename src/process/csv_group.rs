@@ -0,0 +1,210 @@
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+
+use crate::cli::{AggOp, Aggregation, CsvReaderOpts, CsvTransformOpts, InputFormat};
+
+use super::{
+    csv_filter::compare_values,
+    csv_reader::{read_records, Records},
+    csv_transform::{row_key, transform_records},
+};
+
+struct Group {
+    keys: Vec<Value>,
+    count: u64,
+    accs: Vec<Accumulator>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    count: u64,
+    numeric: u64,
+    sum: f64,
+    min: Option<Value>,
+    max: Option<Value>,
+}
+
+/// Group the rows by the `by` columns, the output has the group keys, a `count`
+/// column and one `op(column)` column per aggregation, in first-seen group order.
+pub fn process_csv_group(
+    input: &str,
+    opts: &CsvReaderOpts,
+    by: &[String],
+    aggs: &[Aggregation],
+    transform: &CsvTransformOpts,
+) -> Result<Records> {
+    let records = read_records(input, InputFormat::Csv, opts, "")?;
+    let records = group_records(records, by, aggs)?;
+    transform_records(records, transform)
+}
+
+fn group_records(records: Records, by: &[String], aggs: &[Aggregation]) -> Result<Records> {
+    let columns = by.iter().chain(aggs.iter().map(|agg| &agg.column));
+    if let Some(name) = columns
+        .into_iter()
+        .find(|name| !records.headers.contains(name))
+    {
+        anyhow::bail!("Column not found: {}", name);
+    }
+
+    let mut groups: IndexMap<Vec<String>, Group> = IndexMap::new();
+    for row in records.rows {
+        let row = row?;
+        let group = groups.entry(row_key(&row, by)).or_insert_with(|| Group {
+            keys: by
+                .iter()
+                .map(|c| row.get(c).cloned().unwrap_or(Value::Null))
+                .collect(),
+            count: 0,
+            accs: aggs.iter().map(|_| Accumulator::default()).collect(),
+        });
+        group.count += 1;
+        for (acc, agg) in group.accs.iter_mut().zip(aggs) {
+            acc.update(row.get(&agg.column).unwrap_or(&Value::Null));
+        }
+    }
+
+    let mut headers = by.to_vec();
+    headers.push("count".to_string());
+    headers.extend(aggs.iter().map(|agg| agg.to_string()));
+
+    let out_headers = headers.clone();
+    let rows = groups.into_values().map(move |group| {
+        let mut values = group.keys;
+        values.push(Value::from(group.count));
+        for (acc, agg) in group.accs.into_iter().zip(aggs) {
+            values.push(acc.finish(agg.op));
+        }
+        let row = out_headers
+            .iter()
+            .cloned()
+            .zip(values)
+            .collect::<Map<_, _>>();
+        Ok(Value::Object(row))
+    });
+    let rows = rows.collect::<Vec<_>>();
+    Ok(Records::new(headers, Box::new(rows.into_iter())))
+}
+
+impl Accumulator {
+    /// Nulls and empty cells are skipped, sum/avg only count the numeric cells
+    fn update(&mut self, value: &Value) {
+        if value.is_null() || value.as_str() == Some("") {
+            return;
+        }
+        self.count += 1;
+        let number = match value {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+        if let Some(n) = number {
+            self.numeric += 1;
+            self.sum += n;
+        }
+        let is_less = |current: &Value| {
+            compare_values(value, current).is_some_and(|o| o == std::cmp::Ordering::Less)
+        };
+        if self.min.as_ref().is_none_or(is_less) {
+            self.min = Some(value.clone());
+        }
+        let is_greater = |current: &Value| {
+            compare_values(value, current).is_some_and(|o| o == std::cmp::Ordering::Greater)
+        };
+        if self.max.as_ref().is_none_or(is_greater) {
+            self.max = Some(value.clone());
+        }
+    }
+
+    fn finish(self, op: AggOp) -> Value {
+        match op {
+            AggOp::Count => Value::from(self.count),
+            AggOp::Sum if self.numeric > 0 => number_value(self.sum),
+            AggOp::Avg if self.numeric > 0 => number_value(self.sum / self.numeric as f64),
+            AggOp::Sum | AggOp::Avg => Value::Null,
+            AggOp::Min => self.min.unwrap_or(Value::Null),
+            AggOp::Max => self.max.unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Integral results are rendered without the trailing `.0`
+pub fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        Value::from(n as i64)
+    } else {
+        Value::from(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::SortKey;
+    use serde_json::json;
+
+    #[test]
+    fn test_process_csv_group() -> Result<()> {
+        let aggs = vec![
+            Aggregation {
+                op: AggOp::Sum,
+                column: "Kit Number".into(),
+            },
+            Aggregation {
+                op: AggOp::Max,
+                column: "Kit Number".into(),
+            },
+            Aggregation {
+                op: AggOp::Avg,
+                column: "Kit Number".into(),
+            },
+        ];
+        let transform = CsvTransformOpts {
+            sort_by: vec![SortKey {
+                column: "count".into(),
+                desc: true,
+            }],
+            ..Default::default()
+        };
+        let records = process_csv_group(
+            "assets/juventus.csv",
+            &Default::default(),
+            &["Nationality".to_string()],
+            &aggs,
+            &transform,
+        )?;
+        assert_eq!(
+            records.headers,
+            vec![
+                "Nationality",
+                "count",
+                "sum(Kit Number)",
+                "max(Kit Number)",
+                "avg(Kit Number)"
+            ]
+        );
+        let rows = records.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows.len(), 14);
+        assert_eq!(rows[0]["Nationality"], "Italy");
+        assert_eq!(rows[0]["max(Kit Number)"], "77");
+        let poland = rows.iter().find(|r| r["Nationality"] == "Poland").unwrap();
+        assert_eq!(
+            poland,
+            &json!({
+                "Nationality": "Poland",
+                "count": 1,
+                "sum(Kit Number)": 1,
+                "max(Kit Number)": "1",
+                "avg(Kit Number)": 1
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_value() {
+        assert_eq!(number_value(3.0), json!(3));
+        assert_eq!(number_value(2.5), json!(2.5));
+    }
+}
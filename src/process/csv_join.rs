@@ -0,0 +1,194 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+
+use crate::cli::{CsvReaderOpts, CsvTransformOpts, InputFormat, JoinType};
+
+use super::{
+    csv_reader::{read_records, Records},
+    csv_transform::{row_key, transform_records},
+};
+
+/// Hash join: the right file is loaded in memory, the left one is streamed.
+///
+/// The output has the key columns once, then the other left and right columns.
+/// Names found on both sides get the left/right suffix.
+pub fn process_csv_join(
+    left: &str,
+    right: &str,
+    opts: &CsvReaderOpts,
+    on: &[String],
+    how: JoinType,
+    suffixes: (&str, &str),
+    transform: &CsvTransformOpts,
+) -> Result<Records> {
+    let left = read_records(left, InputFormat::Csv, opts, "")?;
+    let right = read_records(right, InputFormat::Csv, opts, "")?;
+    let records = join_records(left, right, on, how, suffixes)?;
+    transform_records(records, transform)
+}
+
+fn join_records(
+    left: Records,
+    right: Records,
+    on: &[String],
+    how: JoinType,
+    (left_suffix, right_suffix): (&str, &str),
+) -> Result<Records> {
+    for (side, headers) in [("left", &left.headers), ("right", &right.headers)] {
+        if let Some(name) = on.iter().find(|name| !headers.contains(name)) {
+            anyhow::bail!("Join column not found in {} file: {}", side, name);
+        }
+    }
+
+    // (source column, output column) for each side
+    let rename = |headers: &[String], other: &[String], suffix: &str| -> Vec<(String, String)> {
+        headers
+            .iter()
+            .filter(|h| !on.contains(h))
+            .map(|h| {
+                let out = if other.contains(h) {
+                    format!("{}{}", h, suffix)
+                } else {
+                    h.clone()
+                };
+                (h.clone(), out)
+            })
+            .collect()
+    };
+    let left_columns = rename(&left.headers, &right.headers, left_suffix);
+    let right_columns = rename(&right.headers, &left.headers, right_suffix);
+    let mut headers = on.to_vec();
+    headers.extend(left_columns.iter().map(|(_, out)| out.clone()));
+    headers.extend(right_columns.iter().map(|(_, out)| out.clone()));
+
+    let mut index: IndexMap<Vec<String>, Vec<usize>> = IndexMap::new();
+    let right_rows = right.rows.collect::<Result<Vec<_>>>()?;
+    for (i, row) in right_rows.iter().enumerate() {
+        index.entry(row_key(row, on)).or_default().push(i);
+    }
+
+    let joiner = Rc::new(Joiner {
+        on: on.to_vec(),
+        headers: headers.clone(),
+        left_columns,
+        right_columns,
+        right_rows,
+        matched: RefCell::default(),
+    });
+    let keep_unmatched_left = matches!(how, JoinType::Left | JoinType::Outer);
+    let keep_unmatched_right = matches!(how, JoinType::Right | JoinType::Outer);
+
+    let j = joiner.clone();
+    let rows = left.rows.flat_map(move |row| {
+        let ret: Vec<Result<Value>> = match row {
+            Ok(row) => match index.get(&row_key(&row, &j.on)) {
+                Some(matches) => {
+                    j.matched.borrow_mut().extend(matches);
+                    matches
+                        .iter()
+                        .map(|&i| Ok(j.merge(Some(&row), Some(&j.right_rows[i]))))
+                        .collect()
+                }
+                None if keep_unmatched_left => vec![Ok(j.merge(Some(&row), None))],
+                None => vec![],
+            },
+            Err(e) => vec![Err(e)],
+        };
+        ret
+    });
+    // evaluated lazily, once every left row has been matched
+    let unmatched = std::iter::once(()).flat_map(move |_| {
+        let matched = joiner.matched.borrow();
+        let ret: Vec<Result<Value>> = if keep_unmatched_right {
+            (0..joiner.right_rows.len())
+                .filter(|i| !matched.contains(i))
+                .map(|i| Ok(joiner.merge(None, Some(&joiner.right_rows[i]))))
+                .collect()
+        } else {
+            vec![]
+        };
+        ret
+    });
+    Ok(Records::new(headers, Box::new(rows.chain(unmatched))))
+}
+
+struct Joiner {
+    on: Vec<String>,
+    headers: Vec<String>,
+    left_columns: Vec<(String, String)>,
+    right_columns: Vec<(String, String)>,
+    right_rows: Vec<Value>,
+    matched: RefCell<HashSet<usize>>,
+}
+
+impl Joiner {
+    fn merge(&self, left: Option<&Value>, right: Option<&Value>) -> Value {
+        let mut row = Map::with_capacity(self.headers.len());
+        let key_source = left.or(right).expect("one side is always present");
+        for k in &self.on {
+            row.insert(k.clone(), key_source.get(k).cloned().unwrap_or(Value::Null));
+        }
+        for (side, columns) in [(left, &self.left_columns), (right, &self.right_columns)] {
+            for (from, to) in columns {
+                let value = side.and_then(|r| r.get(from)).cloned();
+                row.insert(to.clone(), value.unwrap_or(Value::Null));
+            }
+        }
+        Value::Object(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn join(how: JoinType) -> Result<(Vec<String>, Vec<Value>)> {
+        let records = process_csv_join(
+            "fixtures/join_left.csv",
+            "fixtures/join_right.csv",
+            &Default::default(),
+            &["id".to_string()],
+            how,
+            ("_l", "_r"),
+            &Default::default(),
+        )?;
+        let rows = records.rows.collect::<Result<Vec<_>>>()?;
+        Ok((records.headers, rows))
+    }
+
+    #[test]
+    fn test_inner_join() -> Result<()> {
+        let (headers, rows) = join(JoinType::Inner)?;
+        assert_eq!(headers, vec!["id", "name_l", "team", "name_r", "goals"]);
+        assert_eq!(
+            rows,
+            vec![
+                json!({"id": "1", "name_l": "Dybala", "team": "Juventus", "name_r": "Paulo Dybala", "goals": "10"}),
+                json!({"id": "2", "name_l": "Ronaldo", "team": "Juventus", "name_r": "Cristiano Ronaldo", "goals": "21"}),
+                json!({"id": "2", "name_l": "Ronaldo", "team": "Juventus", "name_r": "Cristiano Ronaldo", "goals": "7"}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_outer_joins() -> Result<()> {
+        let (_, rows) = join(JoinType::Left)?;
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[3]["id"], "3");
+        assert_eq!(rows[3]["goals"], Value::Null);
+
+        let (_, rows) = join(JoinType::Right)?;
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[3]["id"], "4");
+        assert_eq!(rows[3]["name_l"], Value::Null);
+
+        let (_, rows) = join(JoinType::Outer)?;
+        assert_eq!(rows.len(), 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::{csv_types::CellType, csv_writer::value_to_cell, csv_writer::RecordWriter};
+
+/// GitHub pipe table, numeric columns are right aligned
+pub struct MarkdownWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    rows: Vec<Value>,
+}
+
+/// `<table>` with every cell escaped, optionally wrapped in a whole page
+pub struct HtmlWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    standalone: bool,
+}
+
+/// LaTeX `tabular`, numeric columns are right aligned
+pub struct LatexWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    rows: Vec<Value>,
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<style>
+table { border-collapse: collapse; font-family: sans-serif; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; }
+th { background: #f4f4f4; }
+</style>
+</head>
+<body>
+"#;
+
+const HTML_TAIL: &str = "</body>\n</html>\n";
+
+impl MarkdownWriter {
+    pub fn new(headers: &[String], out: Box<dyn Write>) -> Self {
+        Self {
+            out,
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+impl HtmlWriter {
+    pub fn new(headers: &[String], standalone: bool, mut out: Box<dyn Write>) -> Result<Self> {
+        if standalone {
+            out.write_all(HTML_HEAD.as_bytes())?;
+        }
+        writeln!(out, "<table>\n  <thead>\n    <tr>")?;
+        for h in headers {
+            writeln!(out, "      <th>{}</th>", escape_html(h))?;
+        }
+        writeln!(out, "    </tr>\n  </thead>\n  <tbody>")?;
+        Ok(Self {
+            out,
+            headers: headers.to_vec(),
+            standalone,
+        })
+    }
+}
+
+impl LatexWriter {
+    pub fn new(headers: &[String], out: Box<dyn Write>) -> Self {
+        Self {
+            out,
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+impl RecordWriter for MarkdownWriter {
+    // the alignment row depends on every value of the column
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.rows.push(record.clone());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let header = self.headers.iter().map(|h| escape_markdown(h)).collect();
+        self.out.write_all(line(header).as_bytes())?;
+        let align = numeric_columns(&self.headers, &self.rows)
+            .into_iter()
+            .map(|numeric| if numeric { "---:" } else { "---" }.to_string())
+            .collect();
+        self.out.write_all(line(align).as_bytes())?;
+        for row in &self.rows {
+            let cells = cells(&self.headers, row).map(|c| escape_markdown(&c));
+            self.out.write_all(line(cells.collect()).as_bytes())?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for HtmlWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        writeln!(self.out, "    <tr>")?;
+        for h in &self.headers {
+            let value = record.get(h).unwrap_or(&Value::Null);
+            let cell = value_to_cell(value);
+            // rows are streamed, so alignment is decided per cell
+            let numeric = is_numeric_cell(&cell);
+            let cell = escape_html(&cell);
+            if numeric {
+                writeln!(
+                    self.out,
+                    "      <td style=\"text-align: right\">{}</td>",
+                    cell
+                )?;
+            } else {
+                writeln!(self.out, "      <td>{}</td>", cell)?;
+            }
+        }
+        writeln!(self.out, "    </tr>")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        writeln!(self.out, "  </tbody>\n</table>")?;
+        if self.standalone {
+            self.out.write_all(HTML_TAIL.as_bytes())?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for LatexWriter {
+    // the column spec depends on every value of the column
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.rows.push(record.clone());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let spec: String = numeric_columns(&self.headers, &self.rows)
+            .into_iter()
+            .map(|numeric| if numeric { 'r' } else { 'l' })
+            .collect();
+        writeln!(self.out, "\\begin{{tabular}}{{{}}}\n\\hline", spec)?;
+        let header: Vec<String> = self.headers.iter().map(|h| escape_latex(h)).collect();
+        writeln!(self.out, "{} \\\\\n\\hline", header.join(" & "))?;
+        for row in &self.rows {
+            let cells: Vec<String> = cells(&self.headers, row)
+                .map(|c| escape_latex(&c))
+                .collect();
+            writeln!(self.out, "{} \\\\", cells.join(" & "))?;
+        }
+        writeln!(self.out, "\\hline\n\\end{{tabular}}")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn cells<'a>(headers: &'a [String], row: &'a Value) -> impl Iterator<Item = String> + 'a {
+    headers
+        .iter()
+        .map(move |h| row.get(h).map(value_to_cell).unwrap_or_default())
+}
+
+/// A column is numeric when it has a value and every non-empty cell is a number
+fn numeric_columns(headers: &[String], rows: &[Value]) -> Vec<bool> {
+    headers
+        .iter()
+        .map(|h| {
+            let mut cells = rows
+                .iter()
+                .map(|row| row.get(h).map(value_to_cell).unwrap_or_default())
+                .filter(|c| !c.is_empty())
+                .peekable();
+            cells.peek().is_some() && cells.all(|c| is_numeric_cell(&c))
+        })
+        .collect()
+}
+
+/// Numbers are right-aligned, whether typed or numeric strings
+fn is_numeric_cell(cell: &str) -> bool {
+    CellType::infer(cell).is_some_and(|ty| ty.is_numeric())
+}
+
+/// Pipes would split the cell and `<` could open an HTML tag
+fn escape_markdown(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('<', "&lt;")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_latex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\r' => {}
+            '\n' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::OutputFormat,
+        process::{csv_reader::Records, csv_writer::write_records},
+        utils::TempPath,
+    };
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape_markdown("a|b\nc<d>"), "a\\|b<br>c&lt;d>");
+        assert_eq!(
+            escape_html(r#"<b>"Tom" & 'Jerry'</b>"#),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;"
+        );
+        assert_eq!(
+            escape_latex("50% of $x_1 & {y}"),
+            "50\\% of \\$x\\_1 \\& \\{y\\}"
+        );
+        assert_eq!(
+            escape_latex("a\\b~"),
+            "a\\textbackslash{}b\\textasciitilde{}"
+        );
+    }
+
+    #[test]
+    fn test_numeric_columns() {
+        let headers = vec!["name".to_string(), "kit".to_string(), "note".to_string()];
+        let rows = vec![
+            serde_json::json!({"name": "Dybala", "kit": "10", "note": null}),
+            serde_json::json!({"name": "Buffon", "kit": 77, "note": ""}),
+            serde_json::json!({"name": "7", "kit": "", "note": null}),
+        ];
+        assert_eq!(numeric_columns(&headers, &rows), [false, true, false]);
+    }
+
+    #[test]
+    fn test_html_aligns_numeric_strings() -> Result<()> {
+        let output = TempPath::new(".html");
+        let rows = vec![Ok(serde_json::json!({"name": "Dybala", "kit": "10"}))];
+        let records = Records::new(
+            vec!["name".into(), "kit".into()],
+            Box::new(rows.into_iter()),
+        );
+        write_records(records, &output, OutputFormat::Html, &Default::default())?;
+        let content = std::fs::read_to_string(&output)?;
+        assert!(content.contains("<td>Dybala</td>"));
+        assert!(content.contains("<td style=\"text-align: right\">10</td>"));
+        Ok(())
+    }
+}
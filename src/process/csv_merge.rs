@@ -0,0 +1,150 @@
+use std::iter;
+
+use anyhow::Result;
+use indexmap::IndexSet;
+use serde_json::{Map, Value};
+
+use crate::cli::{CsvReaderOpts, InputFormat};
+
+use super::csv_reader::{read_records, Records};
+
+/// Concatenate CSV files, aligning columns by header name: the headers are the
+/// union of every file's headers in first-seen order and missing cells are
+/// null. Files are read one after another, so memory stays bounded.
+///
+/// Inputs may be glob patterns like `data/*.csv`. With several files every
+/// file is read twice, once for its headers and once for its rows, a single
+/// input (which may be stdin) is read once.
+pub fn process_csv_merge(inputs: &[String], opts: &CsvReaderOpts) -> Result<Records> {
+    let files = expand_globs(inputs)?;
+    if let [file] = files.as_slice() {
+        return read_records(file, InputFormat::Csv, opts, "");
+    }
+    let mut headers: IndexSet<String> = IndexSet::new();
+    for file in &files {
+        headers.extend(read_records(file, InputFormat::Csv, opts, "")?.headers);
+    }
+    let headers: Vec<String> = headers.into_iter().collect();
+
+    let opts = opts.clone();
+    let columns = headers.clone();
+    let rows = files
+        .into_iter()
+        .flat_map(
+            move |file| match read_records(&file, InputFormat::Csv, &opts, "") {
+                Ok(records) => records.rows,
+                Err(e) => Box::new(iter::once(Err(e))),
+            },
+        )
+        .map(move |row| {
+            let row = row?;
+            let aligned: Map<String, Value> = columns
+                .iter()
+                .map(|h| (h.clone(), row.get(h).cloned().unwrap_or(Value::Null)))
+                .collect();
+            Ok(Value::Object(aligned))
+        });
+    Ok(Records::new(headers, Box::new(rows)))
+}
+
+/// Expand glob patterns to the matching files in order, plain paths are kept
+/// as they are. A pattern matching nothing is an error.
+pub fn expand_globs(patterns: &[String]) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            files.push(pattern.clone());
+            continue;
+        }
+        let matched = glob::glob(pattern)?
+            .map(|path| Ok(path?.to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        if matched.is_empty() {
+            anyhow::bail!("No file matches: {}", pattern);
+        }
+        files.extend(matched);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cli::OnError, utils::TempPath};
+    use serde_json::json;
+
+    #[test]
+    fn test_expand_globs() -> Result<()> {
+        let files = expand_globs(&["fixtures/join_*.csv".into(), "assets/juventus.csv".into()])?;
+        assert_eq!(
+            files,
+            [
+                "fixtures/join_left.csv",
+                "fixtures/join_right.csv",
+                "assets/juventus.csv"
+            ]
+        );
+        assert!(expand_globs(&["fixtures/nope_*.csv".into()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_merge() -> Result<()> {
+        let inputs = vec!["fixtures/join_*.csv".into()];
+        let records = process_csv_merge(&inputs, &Default::default())?;
+        assert_eq!(records.headers, ["id", "name", "team", "goals"]);
+        let rows = records.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows.len(), 3 + 4);
+        assert_eq!(
+            rows[0],
+            json!({"id": "1", "name": "Dybala", "team": "Juventus", "goals": null})
+        );
+        assert_eq!(
+            rows[3],
+            json!({"id": "1", "name": "Paulo Dybala", "team": null, "goals": "10"})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_merge_rejects() -> Result<()> {
+        let reject_file = TempPath::new(".csv");
+        let opts = CsvReaderOpts {
+            on_error: OnError::Report,
+            reject_file: reject_file.to_string(),
+            ..Default::default()
+        };
+        let inputs = vec![
+            "fixtures/players_malformed.csv".into(),
+            "fixtures/players_stray_byte.csv".into(),
+        ];
+        let records = process_csv_merge(&inputs, &opts)?;
+        assert_eq!(records.rows.count(), 3 + 3);
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(&reject_file)?;
+        // the rejected rows themselves may not be valid UTF-8
+        let rejects = reader
+            .byte_records()
+            .map(|row| {
+                let row = row?;
+                Ok(format!(
+                    "{}:{}",
+                    String::from_utf8_lossy(&row[0]),
+                    String::from_utf8_lossy(&row[1])
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // every file's rejects are kept, each with the file it came from
+        assert_eq!(
+            rejects,
+            [
+                "fixtures/players_malformed.csv:3",
+                "fixtures/players_malformed.csv:5",
+                "fixtures/players_malformed.csv:6",
+                "fixtures/players_stray_byte.csv:4",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use indexmap::IndexSet;
+use serde_json::{Map, Value};
+
+use super::csv_reader::Records;
+
+/// Arrays are filled up to the index with nulls, so a larger index in a
+/// header is taken as malformed rather than allocating a huge array
+const MAX_INDEX: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Build nested objects and arrays from headers like `address.city` or
+/// `tags[0]`, the inverse of the flattening done for structured input. Cells
+/// holding a JSON-encoded array or object are decoded as well.
+pub fn nest_records(records: Records) -> Result<Records> {
+    let paths: Vec<Vec<Segment>> = records.headers.iter().map(|h| parse_path(h)).collect();
+    let headers: IndexSet<String> = paths
+        .iter()
+        .map(|path| match &path[0] {
+            Segment::Key(k) => k.clone(),
+            Segment::Index(_) => unreachable!("paths always start with a key"),
+        })
+        .collect();
+
+    let columns = records.headers;
+    let rows = records.rows.map(move |row| {
+        let row = row?;
+        let mut root = Value::Object(Map::new());
+        for (column, path) in columns.iter().zip(&paths) {
+            let value = row.get(column).cloned().unwrap_or(Value::Null);
+            insert(&mut root, path, decode(value))
+                .map_err(|_| anyhow::anyhow!("Conflicting column for nesting: {}", column))?;
+        }
+        Ok(root)
+    });
+    Ok(Records {
+        headers: headers.into_iter().collect(),
+        rows: Box::new(rows),
+        summary: records.summary,
+    })
+}
+
+/// Split `a.b[1].c` into segments, anything malformed, an index above
+/// `MAX_INDEX` included, is kept as a single key
+fn parse_path(header: &str) -> Vec<Segment> {
+    let mut path = Vec::new();
+    for part in header.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(i) => part.split_at(i),
+            None => (part, ""),
+        };
+        if key.is_empty() {
+            return vec![Segment::Key(header.to_string())];
+        }
+        path.push(Segment::Key(key.to_string()));
+        while !rest.is_empty() {
+            let index = rest
+                .strip_prefix('[')
+                .and_then(|r| r.split_once(']'))
+                .and_then(|(i, r)| i.parse::<usize>().ok().map(|i| (i, r)))
+                .filter(|(i, _)| *i <= MAX_INDEX);
+            match index {
+                Some((i, r)) => {
+                    path.push(Segment::Index(i));
+                    rest = r;
+                }
+                None => return vec![Segment::Key(header.to_string())],
+            }
+        }
+    }
+    path
+}
+
+fn insert(node: &mut Value, path: &[Segment], value: Value) -> Result<(), ()> {
+    let Some((segment, rest)) = path.split_first() else {
+        *node = value;
+        return Ok(());
+    };
+    // an empty slot takes the shape the next segment needs
+    let empty = match rest.first() {
+        Some(Segment::Index(_)) => Value::Array(vec![]),
+        Some(Segment::Key(_)) => Value::Object(Map::new()),
+        None => Value::Null,
+    };
+    let child = match (segment, node) {
+        (Segment::Key(k), Value::Object(obj)) => obj.entry(k.clone()).or_insert(empty),
+        (Segment::Index(i), Value::Array(items)) => {
+            if items.len() <= *i {
+                items.resize(*i + 1, Value::Null);
+            }
+            let child = &mut items[*i];
+            if child.is_null() {
+                *child = empty;
+            }
+            child
+        }
+        _ => return Err(()),
+    };
+    if rest.is_empty() && !child.is_null() {
+        return Err(());
+    }
+    insert(child, rest, value)
+}
+
+fn decode(value: Value) -> Value {
+    match &value {
+        Value::String(s) if s.starts_with('[') || s.starts_with('{') => {
+            serde_json::from_str(s).unwrap_or(value)
+        }
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn nest(headers: &[&str], row: Value) -> Result<(Vec<String>, Value)> {
+        let records = Records::new(
+            headers.iter().map(|h| h.to_string()).collect(),
+            Box::new(std::iter::once(Ok(row))),
+        );
+        let mut records = nest_records(records)?;
+        let row = records.rows.next().unwrap()?;
+        Ok((records.headers, row))
+    }
+
+    #[test]
+    fn test_nest_records() -> Result<()> {
+        let (headers, row) = nest(
+            &[
+                "id",
+                "address.city",
+                "address.geo.lat",
+                "tags[0]",
+                "tags[2]",
+                "servers[0].host",
+                "roles",
+            ],
+            json!({
+                "id": "1",
+                "address.city": "Turin",
+                "address.geo.lat": 45.07,
+                "tags[0]": "admin",
+                "tags[2]": "dev",
+                "servers[0].host": "a.example.com",
+                "roles": "[\"ops\"]"
+            }),
+        )?;
+        assert_eq!(headers, vec!["id", "address", "tags", "servers", "roles"]);
+        assert_eq!(
+            row,
+            json!({
+                "id": "1",
+                "address": {"city": "Turin", "geo": {"lat": 45.07}},
+                "tags": ["admin", null, "dev"],
+                "servers": [{"host": "a.example.com"}],
+                "roles": ["ops"]
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_nest_conflict_and_malformed() -> Result<()> {
+        assert!(nest(&["a", "a.b"], json!({"a": "1", "a.b": "2"})).is_err());
+
+        let (headers, row) = nest(&["a[x]", ".b"], json!({"a[x]": "1", ".b": "2"}))?;
+        assert_eq!(headers, vec!["a[x]", ".b"]);
+        assert_eq!(row, json!({"a[x]": "1", ".b": "2"}));
+
+        let (headers, row) = nest(&["tags[4000000000]"], json!({"tags[4000000000]": "1"}))?;
+        assert_eq!(headers, vec!["tags[4000000000]"]);
+        assert_eq!(row, json!({"tags[4000000000]": "1"}));
+        Ok(())
+    }
+}
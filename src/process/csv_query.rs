@@ -0,0 +1,79 @@
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::cli::{CsvReaderOpts, InputFormat};
+
+use super::{
+    csv_reader::{read_records, Records},
+    csv_sqlite::{load_table, query_records},
+};
+
+/// Load every CSV into an in-memory SQLite database, one table per file, and
+/// run the query against it. Any SQL that SQLite supports works, including
+/// joins, aggregates and subqueries.
+pub fn process_csv_query(
+    sql: &str,
+    tables: &[(String, String)],
+    opts: &CsvReaderOpts,
+) -> Result<Records> {
+    let mut conn = Connection::open_in_memory()?;
+    let tx = conn.transaction()?;
+    for (name, path) in tables {
+        let records = read_records(path, InputFormat::Csv, opts, "")?;
+        let types = vec![None; records.headers.len()];
+        load_table(&tx, name, records, &types)?;
+    }
+    tx.commit()?;
+    query_records(&conn, sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(sql: &str) -> Result<Vec<serde_json::Value>> {
+        let tables = vec![
+            ("juventus".to_string(), "assets/juventus.csv".to_string()),
+            ("goals".to_string(), "fixtures/join_right.csv".to_string()),
+        ];
+        let records = process_csv_query(sql, &tables, &Default::default())?;
+        records.rows.collect()
+    }
+
+    #[test]
+    fn test_query_group_by() -> Result<()> {
+        let rows = query(
+            "SELECT Nationality, count(*) AS n FROM juventus \
+             GROUP BY Nationality ORDER BY n DESC, Nationality LIMIT 2",
+        )?;
+        assert_eq!(
+            rows,
+            vec![
+                json!({"Nationality": "Italy", "n": 8}),
+                json!({"Nationality": "Brazil", "n": 3}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_numeric_where_and_join() -> Result<()> {
+        let rows = query(r#"SELECT Name FROM juventus WHERE "Kit Number" > 33"#)?;
+        assert_eq!(rows.len(), 2);
+
+        let rows = query(
+            "SELECT j.Name, g.goals FROM juventus j JOIN goals g ON g.name = j.Name \
+             ORDER BY j.Name, g.goals",
+        )?;
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], json!({"Name": "Cristiano Ronaldo", "goals": 7}));
+        assert_eq!(rows[3], json!({"Name": "Paulo Dybala", "goals": 10}));
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_error() {
+        assert!(query("SELECT * FROM nope").is_err());
+    }
+}
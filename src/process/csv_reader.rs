@@ -0,0 +1,461 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    fs::File,
+    io::Read,
+    rc::Rc,
+};
+
+use anyhow::Result;
+use csv::{ByteRecord, Reader, ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use indexmap::IndexSet;
+use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::{CsvReaderOpts, InputFormat, OnError},
+    get_reader,
+};
+
+use super::csv_encoding::decode_reader;
+use super::csv_types::{infer_value, load_schema, CellType};
+
+/// A stream of records keyed by header, with the headers in column order
+pub struct Records {
+    pub headers: Vec<String>,
+    pub rows: Box<dyn Iterator<Item = Result<Value>>>,
+    /// Rows read and rejected by the source reader, updated as `rows` is consumed
+    pub summary: Rc<ReadSummary>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReadSummary {
+    pub read: Cell<u64>,
+    pub rejected: Cell<u64>,
+    /// Source line of the last row read, 0 for input without lines like JSON
+    pub line: Cell<u64>,
+}
+
+struct RecordConverter {
+    headers: Vec<String>,
+    types: Vec<Option<CellType>>,
+    infer_types: bool,
+}
+
+/// The `--reject-file` of a command, created by the first reader that reports
+/// and shared with the others so rejects of every input end up in one file
+#[derive(Clone, Default)]
+pub struct RejectSink(Rc<RefCell<Option<Writer<File>>>>);
+
+/// Where malformed rows go according to `--on-error`
+struct Rejects {
+    mode: OnError,
+    input: String,
+    /// Byte offsets are into the UTF-8 text, not the source file
+    transcoded: bool,
+    out: Option<RejectSink>,
+    summary: Rc<ReadSummary>,
+}
+
+impl Records {
+    pub fn new(headers: Vec<String>, rows: Box<dyn Iterator<Item = Result<Value>>>) -> Self {
+        Self {
+            headers,
+            rows,
+            summary: Default::default(),
+        }
+    }
+}
+
+pub fn read_records(
+    input: &str,
+    format: InputFormat,
+    opts: &CsvReaderOpts,
+    toml_key: &str,
+) -> Result<Records> {
+    match format {
+        InputFormat::Csv => read_csv(input, opts),
+        _ => read_structured(input, format, toml_key),
+    }
+}
+
+fn read_csv(input: &str, opts: &CsvReaderOpts) -> Result<Records> {
+    let (decoded, transcoded) = decode_reader(progress_reader(input)?, opts.encoding)?;
+    // ragged rows are checked by the converter so they can be skipped or reported
+    let mut reader = ReaderBuilder::new()
+        .delimiter(opts.delimiter)
+        .has_headers(opts.header)
+        .flexible(true)
+        .from_reader(decoded);
+    let headers = read_headers(&mut reader, opts)?;
+    let converter = RecordConverter {
+        types: column_types(&headers, opts)?,
+        headers: headers.iter().map(String::from).collect(),
+        infer_types: opts.infer_types,
+    };
+    let summary = Rc::new(ReadSummary::default());
+    let mut rejects = Rejects::new(input, opts, transcoded, summary.clone())?;
+    let rows = reader.into_byte_records().filter_map(move |result| {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e.into())),
+        };
+        rejects.summary.read.set(rejects.summary.read.get() + 1);
+        let line = record.position().map(|pos| pos.line()).unwrap_or_default();
+        rejects.summary.line.set(line);
+        match converter.convert(&record) {
+            Ok(value) => Some(Ok(value)),
+            Err(reason) => rejects.reject(&record, reason).err().map(Err),
+        }
+    });
+    Ok(Records {
+        headers: headers.iter().map(String::from).collect(),
+        rows: Box::new(rows),
+        summary,
+    })
+}
+
+/// Open the input, reporting the progress on stderr when it's a file of known size
+fn progress_reader(input: &str) -> Result<Box<dyn Read>> {
+    if input == "-" {
+        return get_reader(input);
+    }
+    let file = File::open(input)?;
+    let pb = ProgressBar::new(file.metadata()?.len())
+        .with_style(ProgressStyle::with_template(
+            "{spinner} [{elapsed_precise}] [{bar:40}] {bytes}/{total_bytes} ({eta})",
+        )?)
+        .with_finish(ProgressFinish::AndClear);
+    Ok(Box::new(pb.wrap_read(file)))
+}
+
+impl RecordConverter {
+    fn convert(&self, record: &ByteRecord) -> Result<Value> {
+        if record.len() != self.headers.len() {
+            anyhow::bail!(
+                "expected {} fields, found {}",
+                self.headers.len(),
+                record.len()
+            );
+        }
+        let record = StringRecord::from_byte_record(record.clone()).map_err(|e| {
+            let col = e.utf8_error().field();
+            anyhow::anyhow!(
+                "invalid UTF-8 in column {} ({})",
+                col + 1,
+                self.headers[col]
+            )
+        })?;
+        let mut json_value = Map::with_capacity(self.headers.len());
+        for (col, (k, v)) in self.headers.iter().zip(record.iter()).enumerate() {
+            let value = match self.types[col] {
+                Some(ty) if v.is_empty() && ty != CellType::String => Value::Null,
+                Some(ty) => ty.parse(v).ok_or_else(|| {
+                    anyhow::anyhow!("invalid {} in column {} ({}): {:?}", ty, col + 1, k, v)
+                })?,
+                None if self.infer_types => infer_value(v),
+                None => Value::String(v.to_string()),
+            };
+            json_value.insert(k.clone(), value);
+        }
+        Ok(Value::Object(json_value))
+    }
+}
+
+impl RejectSink {
+    /// Create the file with its header on first use, later calls keep the writer
+    fn open(&self, path: &str) -> Result<()> {
+        let mut out = self.0.borrow_mut();
+        if out.is_none() {
+            // rejected rows keep their original fields, however many there are
+            let mut writer = WriterBuilder::new().flexible(true).from_path(path)?;
+            writer.write_record(["file", "line", "byte", "decoded_byte", "reason"])?;
+            *out = Some(writer);
+        }
+        Ok(())
+    }
+
+    fn write(&self, row: &ByteRecord) -> Result<()> {
+        if let Some(out) = self.0.borrow_mut().as_mut() {
+            out.write_byte_record(row)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RejectSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RejectSink")
+            .field(&self.0.borrow().is_some())
+            .finish()
+    }
+}
+
+impl Rejects {
+    fn new(
+        input: &str,
+        opts: &CsvReaderOpts,
+        transcoded: bool,
+        summary: Rc<ReadSummary>,
+    ) -> Result<Self> {
+        let out = match opts.on_error {
+            OnError::Report => {
+                opts.rejects.open(&opts.reject_file)?;
+                Some(opts.rejects.clone())
+            }
+            _ => None,
+        };
+        Ok(Self {
+            mode: opts.on_error,
+            input: input.to_string(),
+            transcoded,
+            out,
+            summary,
+        })
+    }
+
+    /// Fail in strict mode, otherwise count the row and record it in report mode
+    fn reject(&mut self, record: &ByteRecord, reason: anyhow::Error) -> Result<()> {
+        let (line, byte) = record
+            .position()
+            .map(|pos| (pos.line(), pos.byte()))
+            .unwrap_or_default();
+        if self.mode == OnError::Strict {
+            let of = if self.transcoded {
+                " of the decoded UTF-8"
+            } else {
+                ""
+            };
+            anyhow::bail!(
+                "Invalid row at line {} (byte {}{}): {}",
+                line,
+                byte,
+                of,
+                reason
+            );
+        }
+        self.summary.rejected.set(self.summary.rejected.get() + 1);
+        if let Some(out) = &self.out {
+            // only one of the offset columns applies to a given input
+            let (byte, decoded_byte) = if self.transcoded {
+                (String::new(), byte.to_string())
+            } else {
+                (byte.to_string(), String::new())
+            };
+            let mut row = ByteRecord::new();
+            row.push_field(self.input.as_bytes());
+            row.push_field(line.to_string().as_bytes());
+            row.push_field(byte.as_bytes());
+            row.push_field(decoded_byte.as_bytes());
+            row.push_field(reason.to_string().as_bytes());
+            row.extend(record.iter());
+            out.write(&row)?;
+        }
+        Ok(())
+    }
+}
+
+/// Column types declared by `--schema`, in header order
+fn column_types(headers: &StringRecord, opts: &CsvReaderOpts) -> Result<Vec<Option<CellType>>> {
+    let schema = match &opts.schema {
+        Some(path) => load_schema(path)?,
+        None => Default::default(),
+    };
+    if let Some(name) = schema
+        .keys()
+        .find(|name| !headers.iter().any(|h| h == *name))
+    {
+        anyhow::bail!("Schema column not found in CSV headers: {}", name);
+    }
+    Ok(headers.iter().map(|h| schema.get(h).copied()).collect())
+}
+
+/// Read an array of objects from JSON/YAML/TOML/NDJSON and flatten every
+/// object, the headers are the union of the flattened keys in first-seen order.
+///
+/// The headers are only known once every object is seen, so unlike CSV input
+/// the whole document is loaded in memory.
+fn read_structured(input: &str, format: InputFormat, toml_key: &str) -> Result<Records> {
+    let mut content = String::new();
+    get_reader(input)?.read_to_string(&mut content)?;
+    let items = match format {
+        InputFormat::Json => serde_json::from_str(&content)?,
+        InputFormat::Yaml => serde_yaml::from_str(&content)?,
+        InputFormat::Toml => {
+            let mut table: toml::Table = toml::from_str(&content)?;
+            let items = table
+                .remove(toml_key)
+                .ok_or_else(|| anyhow::anyhow!("Key not found in TOML input: {}", toml_key))?;
+            toml_to_json(items)
+        }
+        InputFormat::Ndjson => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?
+            .into(),
+        InputFormat::Csv => unreachable!("CSV input is handled by read_csv"),
+    };
+    let Value::Array(items) = items else {
+        anyhow::bail!("Expected an array of objects in {} input", format);
+    };
+
+    let records_len = items.len() as u64;
+    let mut headers: IndexSet<String> = IndexSet::new();
+    let mut ret = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        let Value::Object(obj) = item else {
+            anyhow::bail!("Expected an object at index {}, got: {}", i, item);
+        };
+        let mut row = Map::new();
+        flatten("", obj, &mut row);
+        headers.extend(row.keys().cloned());
+        ret.push(Value::Object(row));
+    }
+    let records = Records::new(
+        headers.into_iter().collect(),
+        Box::new(ret.into_iter().map(Ok)),
+    );
+    records.summary.read.set(records_len);
+    Ok(records)
+}
+
+/// Dates and times are kept as their TOML text, `1979-05-27`
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        // NaN and infinities have no JSON number
+        toml::Value::Float(f) => serde_json::Number::from_f64(f)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(f.to_string())),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Flatten nested objects into dotted keys, arrays are JSON-encoded as a single cell
+fn flatten(prefix: &str, obj: Map<String, Value>, out: &mut Map<String, Value>) {
+    for (k, v) in obj {
+        let key = if prefix.is_empty() {
+            k
+        } else {
+            format!("{}.{}", prefix, k)
+        };
+        match v {
+            Value::Object(nested) if !nested.is_empty() => flatten(&key, nested, out),
+            Value::Object(_) | Value::Array(_) => {
+                out.insert(key, Value::String(v.to_string()));
+            }
+            v => {
+                out.insert(key, v);
+            }
+        }
+    }
+}
+
+/// Resolve the column names: `--columns` wins, then the header row, and
+/// headerless files fall back to `col1..colN`.
+fn read_headers<R: Read>(reader: &mut Reader<R>, opts: &CsvReaderOpts) -> Result<StringRecord> {
+    // without a header row, `headers()` peeks at the first record
+    let width = reader.headers()?.len();
+    let headers = match &opts.columns {
+        Some(columns) if columns.len() != width => anyhow::bail!(
+            "Expected {} column names, got {}: {}",
+            width,
+            columns.len(),
+            columns.join(",")
+        ),
+        Some(columns) => columns.iter().collect(),
+        None if opts.header => reader.headers()?.clone(),
+        None => (1..=width).map(|i| format!("col{}", i)).collect(),
+    };
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_records_is_lazy() -> Result<()> {
+        let records = read_records(
+            "assets/juventus.csv",
+            InputFormat::Csv,
+            &Default::default(),
+            "records",
+        )?;
+        assert_eq!(records.headers.len(), 5);
+        let first = records.rows.take(1).collect::<Result<Vec<_>>>()?;
+        assert_eq!(first[0]["Name"], "Wojciech Szczesny");
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_tracks_source_line() -> Result<()> {
+        let opts = CsvReaderOpts {
+            on_error: OnError::Skip,
+            ..Default::default()
+        };
+        let records = read_records(
+            "fixtures/players_malformed.csv",
+            InputFormat::Csv,
+            &opts,
+            "",
+        )?;
+        let summary = records.summary.clone();
+        let lines = records
+            .rows
+            .map(|row| Ok((row?["Name"].clone(), summary.line.get())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            lines,
+            [
+                (Value::from("Wojciech Szczesny"), 2),
+                (Value::from("Gianluigi Buffon"), 4),
+                (Value::from("Leonardo Bonucci"), 7)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_toml_dates() -> Result<()> {
+        let records = read_records(
+            "fixtures/players.toml",
+            InputFormat::Toml,
+            &Default::default(),
+            "records",
+        )?;
+        assert_eq!(
+            records.headers,
+            ["name", "born", "kit", "contract.until", "training"]
+        );
+        let rows = records.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows[0]["born"], "1978-01-28");
+        assert_eq!(rows[0]["contract.until"], "2021-06-30T23:59:59Z");
+        assert_eq!(rows[0]["kit"], 77);
+        assert_eq!(rows[1]["training"], "09:30:00");
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten() {
+        let obj = serde_json::json!({"a": {"b": {"c": 1}, "d": []}, "e": {}});
+        let Value::Object(obj) = obj else { panic!() };
+        let mut out = Map::new();
+        flatten("", obj, &mut out);
+        assert_eq!(
+            Value::Object(out),
+            serde_json::json!({"a.b.c": 1, "a.d": "[]", "e": "{}"})
+        );
+    }
+}
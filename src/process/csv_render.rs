@@ -0,0 +1,185 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    path::{Component, Path},
+};
+
+use anyhow::Result;
+use minijinja::{Environment, UndefinedBehavior};
+use serde_json::{json, Value};
+
+use crate::get_writer;
+
+use super::csv_reader::Records;
+
+/// Render the template once per row, the row's columns are variables of the
+/// template (`{{ Name }}`), also available as `row` for names that aren't
+/// identifiers (`{{ row["Kit Number"] }}`), with the 1-based `index`.
+///
+/// The output path of every row is rendered from `output_pattern` with the
+/// same variables, missing directories are created. Row data can't move the
+/// path out of where the pattern points, see `check_path`. Returns the files
+/// written.
+pub fn process_csv_render_rows(
+    records: Records,
+    template: &str,
+    output_pattern: &str,
+) -> Result<Vec<String>> {
+    let env = environment(template, Some(output_pattern))?;
+    let (tpl, path_tpl) = (env.get_template("template")?, env.get_template("path")?);
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for (i, row) in records.rows.enumerate() {
+        let ctx = row_context(row?, i + 1);
+        let path = path_tpl.render(&ctx)?;
+        check_path(output_pattern, &path)?;
+        if !seen.insert(path.clone()) {
+            anyhow::bail!(
+                "Output pattern renders the same path for several rows: {}",
+                path
+            );
+        }
+        if let Some(dir) = Path::new(&path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = get_writer(&path)?;
+        out.write_all(tpl.render(&ctx)?.as_bytes())?;
+        out.flush()?;
+        files.push(path);
+    }
+    Ok(files)
+}
+
+/// Render the template once for the whole dataset, with `rows` (every row as
+/// an object) and `headers` as variables
+pub fn process_csv_render(records: Records, template: &str, output: &str) -> Result<()> {
+    let env = environment(template, None)?;
+    let rows = records.rows.collect::<Result<Vec<_>>>()?;
+    let ctx = json!({"rows": rows, "headers": records.headers});
+    let mut out = get_writer(output)?;
+    let content = env.get_template("template")?.render(&ctx)?;
+    out.write_all(content.as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Reject a rendered path with more `..` components than the pattern itself,
+/// or an absolute one from a relative pattern, so a cell like `../../etc/x`
+/// can't write outside the output directory
+fn check_path(pattern: &str, path: &str) -> Result<()> {
+    let parents = |p: &str| {
+        Path::new(p)
+            .components()
+            .filter(|c| *c == Component::ParentDir)
+            .count()
+    };
+    let absolute = Path::new(path).is_absolute() && !Path::new(pattern).is_absolute();
+    if absolute || parents(path) > parents(pattern) {
+        anyhow::bail!(
+            "Rendered output path escapes the output directory: {}",
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Undefined variables are errors so a misspelled column doesn't silently
+/// render as an empty string
+fn environment<'a>(template: &'a str, output_pattern: Option<&'a str>) -> Result<Environment<'a>> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+    env.add_template("template", template)?;
+    if let Some(pattern) = output_pattern {
+        env.add_template("path", pattern)?;
+    }
+    Ok(env)
+}
+
+fn row_context(row: Value, index: usize) -> Value {
+    let mut ctx = match &row {
+        Value::Object(obj) => obj.clone(),
+        _ => Default::default(),
+    };
+    ctx.insert("row".to_string(), row);
+    ctx.insert("index".to_string(), index.into());
+    Value::Object(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cli::InputFormat, process::csv_reader::read_records, utils::TempPath};
+
+    fn juventus() -> Result<Records> {
+        let opts = crate::cli::CsvReaderOpts {
+            infer_types: true,
+            ..Default::default()
+        };
+        read_records("assets/juventus.csv", InputFormat::Csv, &opts, "")
+    }
+
+    #[test]
+    fn test_render_rows() -> Result<()> {
+        let dir = TempPath::new("");
+        let pattern = format!("{}/{{{{ Position | lower }}}}/{{{{ index }}}}.txt", &*dir);
+        let template = "{{ Name | upper }} wears {{ row[\"Kit Number\"] }}\
+                        {% if row[\"Kit Number\"] > 9 %} (outfield){% endif %}\n";
+        let files = process_csv_render_rows(juventus()?, template, &pattern)?;
+        assert_eq!(files.len(), 27);
+        assert_eq!(
+            fs::read_to_string(Path::new(&*dir).join("goalkeeper/1.txt"))?,
+            "WOJCIECH SZCZESNY wears 1\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&files[2])?,
+            "GIANLUIGI BUFFON wears 77 (outfield)\n"
+        );
+
+        // every row would go to the same file
+        let pattern = format!("{}/{{{{ Position }}}}.txt", &*dir);
+        assert!(process_csv_render_rows(juventus()?, template, &pattern).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_path_escape() -> Result<()> {
+        let dir = TempPath::new("");
+        let render = |name: &str, pattern: &str| {
+            let rows = vec![Ok(json!({ "Name": name }))];
+            let records = Records::new(vec!["Name".into()], Box::new(rows.into_iter()));
+            process_csv_render_rows(records, "{{ Name }}", pattern)
+        };
+        let pattern = format!("{}/{{{{ Name }}}}.txt", &*dir);
+        assert!(render("../../etc/x", &pattern).is_err());
+        assert!(render("a/../../x", &pattern).is_err());
+        assert!(render("/etc/x", "{{ Name }}.txt").is_err());
+        let files = render("a..b", &pattern)?;
+        assert!(files[0].ends_with("a..b.txt"));
+
+        assert!(check_path("../out/{{ Name }}.txt", "../out/x.txt").is_ok());
+        assert!(check_path("../out/{{ Name }}.txt", "../out/../x.txt").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_whole_dataset() -> Result<()> {
+        let output = TempPath::new(".md");
+        let template = "{{ rows | length }} players\n\
+                        {% for row in rows if row.Nationality == \"Argentina\" %}\
+                        - {{ row.Name }}\n\
+                        {% endfor %}";
+        process_csv_render(juventus()?, template, &output)?;
+        let content = fs::read_to_string(&output)?;
+        assert_eq!(content, "27 players\n- Paulo Dybala\n- Gonzalo Higuaín\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_undefined_variable() -> Result<()> {
+        let output = TempPath::new(".txt");
+        assert!(process_csv_render(juventus()?, "{{ nope.x }}", &output).is_err());
+        Ok(())
+    }
+}
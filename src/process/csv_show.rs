@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use serde_json::Value;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::cli::{CsvReaderOpts, CsvTransformOpts, InputFormat};
+
+use super::{
+    csv_reader::read_records, csv_transform::transform_records, csv_writer::value_to_cell,
+};
+
+pub fn process_csv_show(
+    input: &str,
+    opts: &CsvReaderOpts,
+    transform: &CsvTransformOpts,
+    head: Option<usize>,
+    tail: Option<usize>,
+    max_width: usize,
+) -> Result<String> {
+    let records = read_records(input, InputFormat::Csv, opts, "")?;
+    let records = transform_records(records, transform)?;
+    let rows = match (head, tail) {
+        (Some(n), _) => records.rows.take(n).collect::<Result<Vec<_>>>()?,
+        (None, Some(n)) => {
+            // only keep the last n rows around
+            let mut window = VecDeque::with_capacity(n + 1);
+            for row in records.rows {
+                window.push_back(row?);
+                if window.len() > n {
+                    window.pop_front();
+                }
+            }
+            window.into()
+        }
+        (None, None) => records.rows.collect::<Result<Vec<_>>>()?,
+    };
+    Ok(render_table(&records.headers, &rows, max_width))
+}
+
+/// Render rows as a box-drawn table, columns are aligned by display width so
+/// CJK characters take two cells. Numbers are right aligned.
+pub fn render_table(headers: &[String], rows: &[Value], max_width: usize) -> String {
+    let header_cells: Vec<String> = headers.iter().map(|h| truncate(h, max_width)).collect();
+    let body: Vec<Vec<(String, bool)>> = rows
+        .iter()
+        .map(|row| {
+            headers
+                .iter()
+                .map(|h| {
+                    let value = row.get(h).unwrap_or(&Value::Null);
+                    (
+                        truncate(&value_to_cell(value), max_width),
+                        value.is_number(),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = header_cells.iter().map(|h| h.width()).collect();
+    for row in &body {
+        for (w, (cell, _)) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.width());
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(&border(&widths, '┌', '┬', '┐'));
+    let cells = header_cells.into_iter().map(|h| (h, false));
+    out.push_str(&line(&widths, cells));
+    out.push_str(&border(&widths, '├', '┼', '┤'));
+    for row in body {
+        out.push_str(&line(&widths, row.into_iter()));
+    }
+    out.push_str(&border(&widths, '└', '┴', '┘'));
+    out
+}
+
+fn border(widths: &[usize], left: char, mid: char, right: char) -> String {
+    let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+    format!("{}{}{}\n", left, segments.join(&mid.to_string()), right)
+}
+
+fn line(widths: &[usize], cells: impl Iterator<Item = (String, bool)>) -> String {
+    let mut out = String::from("│");
+    for (w, (cell, right_align)) in widths.iter().zip(cells) {
+        let pad = " ".repeat(w - cell.width());
+        if right_align {
+            out.push_str(&format!(" {}{} │", pad, cell));
+        } else {
+            out.push_str(&format!(" {}{} │", cell, pad));
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// Cut a cell to at most `max_width` display columns, marking the cut with `…`
+fn truncate(s: &str, max_width: usize) -> String {
+    // newlines would break the table layout
+    let s = s.replace(['\r', '\n'], " ");
+    if s.width() <= max_width {
+        return s;
+    }
+    let mut ret = String::new();
+    let mut width = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if width + w + 1 > max_width {
+            break;
+        }
+        ret.push(c);
+        width += w;
+    }
+    ret.push('…');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table_with_cjk() {
+        let headers = vec!["姓名".to_string(), "kit".to_string()];
+        let rows = vec![
+            serde_json::json!({"姓名": "布冯", "kit": 1}),
+            serde_json::json!({"姓名": "Dybala", "kit": 10}),
+        ];
+        let table = render_table(&headers, &rows, 40);
+        assert_eq!(
+            table,
+            "┌────────┬─────┐\n\
+             │ 姓名   │ kit │\n\
+             ├────────┼─────┤\n\
+             │ 布冯   │   1 │\n\
+             │ Dybala │  10 │\n\
+             └────────┴─────┘\n"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", 5), "hello");
+        assert_eq!(truncate("hello world", 5), "hell…");
+        assert_eq!(truncate("布冯布冯", 5), "布冯…");
+    }
+
+    #[test]
+    fn test_process_csv_show_head_tail() -> Result<()> {
+        let opts = CsvReaderOpts::default();
+        let transform = CsvTransformOpts::default();
+        let table = process_csv_show("assets/juventus.csv", &opts, &transform, Some(2), None, 40)?;
+        assert_eq!(table.lines().count(), 6);
+        assert!(table.contains("Mattia Perin"));
+
+        let table = process_csv_show("assets/juventus.csv", &opts, &transform, None, Some(1), 40)?;
+        assert_eq!(table.lines().count(), 5);
+        assert!(table.contains("Mario Mandzukic"));
+        Ok(())
+    }
+}
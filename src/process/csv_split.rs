@@ -0,0 +1,235 @@
+use std::{collections::HashMap, fs::OpenOptions, io::BufWriter, path::Path};
+
+use anyhow::Result;
+use indexmap::IndexMap;
+
+use crate::{
+    cli::{CsvWriterOpts, OutputFormat},
+    get_writer,
+};
+
+use super::{
+    csv_reader::Records,
+    csv_writer::{csv_rows_writer, record_writer, value_to_cell, RecordWriter},
+};
+
+/// Files kept open at once when splitting by column, the least recently used
+/// one is closed to open another and reopened for append when needed
+const MAX_OPEN_FILES: usize = 128;
+
+/// How rows are spread over the output files
+#[derive(Debug, Clone)]
+pub enum SplitBy {
+    Rows(usize),
+    Column(String),
+}
+
+/// Write the records into CSV files under `dir`, named `{prefix}_1.csv`,
+/// `{prefix}_2.csv`... for fixed-size chunks or `{prefix}_{value}.csv` per
+/// column value. Every file starts with the header row. Rows are streamed, at
+/// most `MAX_OPEN_FILES` files are open at a time.
+///
+/// Returns the files written with their row counts, in creation order.
+pub fn process_csv_split(
+    records: Records,
+    by: &SplitBy,
+    dir: &str,
+    prefix: &str,
+    opts: &CsvWriterOpts,
+) -> Result<Vec<(String, u64)>> {
+    if let SplitBy::Column(column) = by {
+        if !records.headers.contains(column) {
+            anyhow::bail!("Split column not found in CSV headers: {}", column);
+        }
+    }
+    if let SplitBy::Rows(0) = by {
+        anyhow::bail!("--rows must be greater than 0");
+    }
+    std::fs::create_dir_all(dir)?;
+
+    let path_of = |suffix: &str| {
+        Path::new(dir)
+            .join(format!("{}_{}.csv", prefix, suffix))
+            .to_string_lossy()
+            .to_string()
+    };
+    let mut files: IndexMap<String, u64> = IndexMap::new();
+    // the column value each file was named after
+    let mut values: HashMap<String, String> = HashMap::new();
+    // in least to most recently used order
+    let mut writers: IndexMap<String, Box<dyn RecordWriter>> = IndexMap::new();
+    for (i, row) in records.rows.enumerate() {
+        let row = row?;
+        let path = match by {
+            SplitBy::Rows(n) => path_of(&(i / n + 1).to_string()),
+            SplitBy::Column(column) => {
+                let value = row.get(column).map(value_to_cell).unwrap_or_default();
+                let path = path_of(&file_suffix(&value));
+                match values.get(&path) {
+                    Some(other) if *other != value => anyhow::bail!(
+                        "Column values {:?} and {:?} both map to the file {}",
+                        other,
+                        value,
+                        path
+                    ),
+                    Some(_) => {}
+                    None => {
+                        values.insert(path.clone(), value);
+                    }
+                }
+                path
+            }
+        };
+        if let Some(index) = writers.get_index_of(&path) {
+            let last = writers.len() - 1;
+            writers.move_index(index, last);
+        } else {
+            // a chunk is complete once the next one starts
+            let limit = match by {
+                SplitBy::Rows(_) => 1,
+                SplitBy::Column(_) => MAX_OPEN_FILES,
+            };
+            while writers.len() >= limit {
+                let (_, writer) = writers.shift_remove_index(0).expect("writers aren't empty");
+                writer.finish()?;
+            }
+            let writer: Box<dyn RecordWriter> = if files.contains_key(&path) {
+                let file = OpenOptions::new().append(true).open(&path)?;
+                Box::new(csv_rows_writer(
+                    &records.headers,
+                    opts,
+                    Box::new(BufWriter::new(file)),
+                ))
+            } else {
+                record_writer(
+                    OutputFormat::Csv,
+                    &records.headers,
+                    opts,
+                    get_writer(&path)?,
+                )?
+            };
+            writers.insert(path.clone(), writer);
+        }
+        writers
+            .get_mut(&path)
+            .expect("writer just inserted")
+            .write(&row)?;
+        *files.entry(path).or_default() += 1;
+    }
+    for (_, writer) in writers {
+        writer.finish()?;
+    }
+    Ok(files.into_iter().collect())
+}
+
+/// Keep a column value usable as part of a file name
+fn file_suffix(value: &str) -> String {
+    if value.is_empty() {
+        return "empty".to_string();
+    }
+    value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::InputFormat;
+    use crate::process::csv_reader::read_records;
+    use crate::utils::TempPath;
+    use serde_json::json;
+    use std::fs;
+
+    fn split(by: SplitBy) -> Result<(TempPath, Vec<(String, u64)>)> {
+        let dir = TempPath::new("");
+        let records = read_records(
+            "assets/juventus.csv",
+            InputFormat::Csv,
+            &Default::default(),
+            "",
+        )?;
+        let files = process_csv_split(records, &by, &dir, "juventus", &Default::default())?;
+        Ok((dir, files))
+    }
+
+    #[test]
+    fn test_split_by_rows() -> Result<()> {
+        let (_dir, files) = split(SplitBy::Rows(10))?;
+        let counts: Vec<u64> = files.iter().map(|(_, n)| *n).collect();
+        assert_eq!(counts, [10, 10, 7]);
+        assert!(files[2].0.ends_with("juventus_3.csv"));
+        let content = fs::read_to_string(&files[2].0)?;
+        assert!(content.starts_with("Name,Position,DOB,Nationality,Kit Number\n"));
+        assert_eq!(content.lines().count(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_column() -> Result<()> {
+        let (_dir, files) = split(SplitBy::Column("Nationality".into()))?;
+        assert_eq!(files.len(), 14);
+        assert_eq!(files.iter().map(|(_, n)| n).sum::<u64>(), 27);
+        let (italy, n) = files
+            .iter()
+            .find(|(f, _)| f.ends_with("juventus_Italy.csv"))
+            .unwrap();
+        assert_eq!(*n, 8);
+        assert_eq!(fs::read_to_string(italy)?.lines().count(), 9);
+        assert!(split(SplitBy::Column("nope".into())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_with_many_values() -> Result<()> {
+        let dir = TempPath::new("");
+        // every value comes back after the file was closed
+        let rows = (0..MAX_OPEN_FILES * 3)
+            .map(|i| Ok(json!({"id": i, "group": i % (MAX_OPEN_FILES + 10)})))
+            .collect::<Vec<_>>();
+        let records = Records::new(
+            vec!["id".into(), "group".into()],
+            Box::new(rows.into_iter()),
+        );
+        let by = SplitBy::Column("group".into());
+        let files = process_csv_split(records, &by, &dir, "g", &Default::default())?;
+        assert_eq!(files.len(), MAX_OPEN_FILES + 10);
+        let content = fs::read_to_string(Path::new(&*dir).join("g_0.csv"))?;
+        let expected = format!(
+            "id,group\n0,0\n{},0\n{},0\n",
+            MAX_OPEN_FILES + 10,
+            2 * (MAX_OPEN_FILES + 10)
+        );
+        assert_eq!(content, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_file_name_collision() -> Result<()> {
+        let dir = TempPath::new("");
+        let rows = vec![Ok(json!({"team": "a/b"})), Ok(json!({"team": "a_b"}))];
+        let records = Records::new(vec!["team".into()], Box::new(rows.into_iter()));
+        let by = SplitBy::Column("team".into());
+        let err = process_csv_split(records, &by, &dir, "col", &Default::default()).unwrap_err();
+        assert!(err.to_string().contains(r#"Column values "a/b" and "a_b""#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_suffix() {
+        assert_eq!(
+            file_suffix("Bosnia and Herzegovina"),
+            "Bosnia_and_Herzegovina"
+        );
+        assert_eq!(file_suffix("a/b"), "a_b");
+        assert_eq!(file_suffix("中国"), "中国");
+        assert_eq!(file_suffix(""), "empty");
+    }
+}
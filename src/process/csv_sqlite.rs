@@ -0,0 +1,105 @@
+use anyhow::Result;
+use rusqlite::{types::Value as SqlValue, Connection};
+use serde_json::{Map, Value};
+
+use super::{
+    csv_reader::Records,
+    csv_types::{infer_value, CellType},
+};
+
+/// Create the table and insert every record. Nothing is committed here, the
+/// caller runs it in a transaction. Untyped columns accept any value, string
+/// cells are stored with their inferred type so `"Kit Number" > 9` compares
+/// numbers.
+pub fn load_table(
+    conn: &Connection,
+    name: &str,
+    records: Records,
+    types: &[Option<CellType>],
+) -> Result<u64> {
+    let columns = records
+        .headers
+        .iter()
+        .zip(types)
+        .map(|(h, ty)| match ty {
+            Some(ty) => format!("{} {}", quote(h), sql_type(*ty)),
+            None => quote(h),
+        })
+        .collect::<Vec<_>>();
+    conn.execute(
+        &format!("CREATE TABLE {} ({})", quote(name), columns.join(", ")),
+        [],
+    )?;
+
+    let insert = format!(
+        "INSERT INTO {} VALUES ({})",
+        quote(name),
+        vec!["?"; records.headers.len()].join(", ")
+    );
+    let mut count = 0;
+    let mut stmt = conn.prepare_cached(&insert)?;
+    for row in records.rows {
+        let row = row?;
+        let values = records
+            .headers
+            .iter()
+            .map(|h| to_sql_value(row.get(h).unwrap_or(&Value::Null)));
+        stmt.execute(rusqlite::params_from_iter(values))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Run a query and collect the result set as records
+pub fn query_records(conn: &Connection, sql: &str) -> Result<Records> {
+    let mut stmt = conn.prepare(sql)?;
+    let headers: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let mut rows = stmt.query([])?;
+    let mut ret = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut obj = Map::with_capacity(headers.len());
+        for (i, h) in headers.iter().enumerate() {
+            obj.insert(h.clone(), from_sql_value(row.get::<_, SqlValue>(i)?));
+        }
+        ret.push(Ok(Value::Object(obj)));
+    }
+    Ok(Records::new(headers, Box::new(ret.into_iter())))
+}
+
+pub fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn sql_type(ty: CellType) -> &'static str {
+    match ty {
+        CellType::Integer | CellType::Boolean => "INTEGER",
+        CellType::Float => "REAL",
+        CellType::String | CellType::Date | CellType::Datetime => "TEXT",
+    }
+}
+
+fn to_sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => match infer_value(s) {
+            Value::String(s) => SqlValue::Text(s),
+            v => to_sql_value(&v),
+        },
+        v => SqlValue::Text(v.to_string()),
+    }
+}
+
+fn from_sql_value(value: SqlValue) -> Value {
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Integer(i) => Value::from(i),
+        SqlValue::Real(f) => Value::from(f),
+        SqlValue::Text(s) => Value::String(s),
+        SqlValue::Blob(b) => Value::String(String::from_utf8_lossy(&b).to_string()),
+    }
+}
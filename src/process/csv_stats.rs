@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::cli::{CsvReaderOpts, InputFormat};
+
+use super::{
+    csv_group::number_value,
+    csv_reader::{read_records, Records},
+    csv_types::CellType,
+    csv_writer::value_to_cell,
+};
+
+const STATS_HEADERS: [&str; 11] = [
+    "column",
+    "type",
+    "count",
+    "nulls",
+    "distinct",
+    "min",
+    "max",
+    "mean",
+    "stddev",
+    "max_length",
+    "top",
+];
+
+#[derive(Default)]
+struct ColumnStats {
+    ty: Option<CellType>,
+    count: u64,
+    nulls: u64,
+    freq: HashMap<String, u64>,
+    min_num: Option<f64>,
+    max_num: Option<f64>,
+    min_str: Option<String>,
+    max_str: Option<String>,
+    // Welford's online mean/variance over the numeric cells
+    n: u64,
+    mean: f64,
+    m2: f64,
+    max_length: usize,
+}
+
+/// Profile every column, one output row per column. Mean and stddev (sample)
+/// are rounded to 4 decimals and only given for numeric columns.
+pub fn process_csv_stats(input: &str, opts: &CsvReaderOpts, top: usize) -> Result<Records> {
+    let records = read_records(input, InputFormat::Csv, opts, "")?;
+    let mut stats: Vec<ColumnStats> = records
+        .headers
+        .iter()
+        .map(|_| ColumnStats::default())
+        .collect();
+    for row in records.rows {
+        let row = row?;
+        for (column, h) in stats.iter_mut().zip(&records.headers) {
+            column.update(&value_to_cell(row.get(h).unwrap_or(&Value::Null)));
+        }
+    }
+
+    let headers: Vec<String> = STATS_HEADERS.iter().map(|h| h.to_string()).collect();
+    let rows = records
+        .headers
+        .iter()
+        .zip(stats)
+        .map(|(name, column)| {
+            let values = column.finish(name, top);
+            Ok(Value::Object(headers.iter().cloned().zip(values).collect()))
+        })
+        .collect::<Vec<_>>();
+    Ok(Records::new(headers, Box::new(rows.into_iter())))
+}
+
+impl ColumnStats {
+    fn update(&mut self, cell: &str) {
+        let Some(ty) = CellType::infer(cell) else {
+            self.nulls += 1;
+            return;
+        };
+        self.count += 1;
+        self.ty = Some(self.ty.map_or(ty, |t| t.unify(ty)));
+        self.max_length = self.max_length.max(cell.chars().count());
+        *self.freq.entry(cell.to_string()).or_default() += 1;
+
+        if let Some(n) = ty.is_numeric().then(|| cell.parse::<f64>().ok()).flatten() {
+            self.min_num = Some(self.min_num.map_or(n, |m| m.min(n)));
+            self.max_num = Some(self.max_num.map_or(n, |m| m.max(n)));
+            self.n += 1;
+            let delta = n - self.mean;
+            self.mean += delta / self.n as f64;
+            self.m2 += delta * (n - self.mean);
+        }
+        if self.min_str.as_deref().is_none_or(|m| cell < m) {
+            self.min_str = Some(cell.to_string());
+        }
+        if self.max_str.as_deref().is_none_or(|m| cell > m) {
+            self.max_str = Some(cell.to_string());
+        }
+    }
+
+    fn finish(self, name: &str, top: usize) -> Vec<Value> {
+        let numeric = self.ty.is_some_and(|t| t.is_numeric());
+        let (min, max) = if numeric {
+            (
+                self.min_num.map(number_value),
+                self.max_num.map(number_value),
+            )
+        } else {
+            (
+                self.min_str.map(Value::String),
+                self.max_str.map(Value::String),
+            )
+        };
+        let (mean, stddev) = if numeric {
+            let stddev = (self.n > 1).then(|| (self.m2 / (self.n - 1) as f64).sqrt());
+            (Some(round(self.mean)), stddev.map(round))
+        } else {
+            (None, None)
+        };
+
+        let distinct = self.freq.len();
+        let mut freq: Vec<(String, u64)> = self.freq.into_iter().collect();
+        freq.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let top = freq
+            .into_iter()
+            .take(top)
+            .map(|(v, c)| (v, Value::from(c)))
+            .collect::<Map<_, _>>();
+
+        vec![
+            Value::from(name),
+            Value::from(self.ty.map_or("null".to_string(), |t| t.to_string())),
+            Value::from(self.count),
+            Value::from(self.nulls),
+            Value::from(distinct),
+            min.unwrap_or(Value::Null),
+            max.unwrap_or(Value::Null),
+            mean.map_or(Value::Null, number_value),
+            stddev.map_or(Value::Null, number_value),
+            Value::from(self.max_length),
+            Value::Object(top),
+        ]
+    }
+}
+
+fn round(n: f64) -> f64 {
+    (n * 10_000.0).round() / 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_process_csv_stats() -> Result<()> {
+        let records = process_csv_stats("assets/juventus.csv", &Default::default(), 2)?;
+        assert_eq!(records.headers, STATS_HEADERS);
+        let rows = records.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows.len(), 5);
+
+        let nationality = &rows[3];
+        assert_eq!(nationality["type"], "string");
+        assert_eq!(nationality["distinct"], 14);
+        assert_eq!(nationality["top"], json!({"Italy": 8, "Brazil": 3}));
+
+        let kit = &rows[4];
+        assert_eq!(kit["type"], "integer");
+        assert_eq!(kit["count"], 27);
+        assert_eq!(kit["nulls"], 0);
+        assert_eq!(kit["min"], 1);
+        assert_eq!(kit["max"], 77);
+        assert_eq!(kit["max_length"], 2);
+        assert!(kit["mean"].as_f64().unwrap() > 1.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::Result;
+use rusqlite::Connection;
+use serde_json::Value;
+
+use crate::cli::{CsvReaderOpts, CsvTransformOpts, InputFormat, OnError};
+
+use super::{
+    csv_reader::{read_records, Records},
+    csv_sqlite::{load_table, quote},
+    csv_transform::transform_records,
+    csv_types::CellType,
+};
+
+/// Load a CSV file into a table of a SQLite database file and index the given
+/// columns, returning the number of rows inserted.
+///
+/// Column types are inferred in a first pass over the file. Standard input
+/// can only be read once, so its columns are left untyped and every value is
+/// stored with the type inferred for that cell.
+pub fn process_csv_to_sqlite(
+    input: &str,
+    output: &str,
+    table: &str,
+    indexes: &[String],
+    replace: bool,
+    opts: &CsvReaderOpts,
+    transform: &CsvTransformOpts,
+) -> Result<u64> {
+    let read =
+        |opts| transform_records(read_records(input, InputFormat::Csv, opts, "")?, transform);
+    let (types, records) = if input == "-" {
+        let records = read(opts)?;
+        check_indexes(indexes, &records)?;
+        (vec![None; records.headers.len()], records)
+    } else {
+        // the load reads the rows again and reports them, so the first pass
+        // only skips the rows it would reject
+        let scan = CsvReaderOpts {
+            on_error: match opts.on_error {
+                OnError::Report => OnError::Skip,
+                mode => mode,
+            },
+            ..opts.clone()
+        };
+        let records = read(&scan)?;
+        check_indexes(indexes, &records)?;
+        (infer_column_types(records)?, read(opts)?)
+    };
+
+    write_table(output, table, records, &types, indexes, replace)
+}
+
+fn check_indexes(indexes: &[String], records: &Records) -> Result<()> {
+    if let Some(name) = indexes.iter().find(|c| !records.headers.contains(c)) {
+        anyhow::bail!("Index column not found in CSV headers: {}", name);
+    }
+    Ok(())
+}
+
+/// Replace the table, insert the records and create the indexes in a single
+/// transaction, so a failed load leaves the database as it was
+fn write_table(
+    output: &str,
+    table: &str,
+    records: Records,
+    types: &[Option<CellType>],
+    indexes: &[String],
+    replace: bool,
+) -> Result<u64> {
+    let mut conn = Connection::open(output)?;
+    let tx = conn.transaction()?;
+    if replace {
+        tx.execute(&format!("DROP TABLE IF EXISTS {}", quote(table)), [])?;
+    }
+    let count = load_table(&tx, table, records, types)?;
+    for column in indexes {
+        tx.execute(
+            &format!(
+                "CREATE INDEX {} ON {} ({})",
+                quote(&format!("idx_{}_{}", table, column)),
+                quote(table),
+                quote(column)
+            ),
+            [],
+        )?;
+    }
+    tx.commit()?;
+    Ok(count)
+}
+
+/// The narrowest type fitting every non-empty cell of each column, all-empty
+/// columns are left untyped
+fn infer_column_types(records: Records) -> Result<Vec<Option<CellType>>> {
+    let mut types: Vec<Option<CellType>> = vec![None; records.headers.len()];
+    for row in records.rows {
+        let row = row?;
+        for (ty, header) in types.iter_mut().zip(&records.headers) {
+            let Some(cell) = row.get(header).and_then(cell_type) else {
+                continue;
+            };
+            *ty = Some(ty.map_or(cell, |t| t.unify(cell)));
+        }
+    }
+    Ok(types)
+}
+
+fn cell_type(value: &Value) -> Option<CellType> {
+    match value {
+        Value::Null => None,
+        Value::Bool(_) => Some(CellType::Boolean),
+        Value::Number(n) if n.is_f64() => Some(CellType::Float),
+        Value::Number(_) => Some(CellType::Integer),
+        Value::String(s) => CellType::infer(s),
+        _ => Some(CellType::String),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempPath;
+
+    #[test]
+    fn test_process_csv_to_sqlite() -> Result<()> {
+        let output = TempPath::new(".db");
+        let indexes = vec!["Nationality".to_string()];
+        let load = |replace| {
+            process_csv_to_sqlite(
+                "assets/juventus.csv",
+                &output,
+                "juventus",
+                &indexes,
+                replace,
+                &Default::default(),
+                &Default::default(),
+            )
+        };
+        assert_eq!(load(false)?, 27);
+        // the table already exists
+        assert!(load(false).is_err());
+        assert_eq!(load(true)?, 27);
+
+        let conn = Connection::open(&output)?;
+        let mut stmt = conn.prepare("SELECT name, type FROM pragma_table_info('juventus')")?;
+        let columns = stmt
+            .query_map([], |row| {
+                Ok(format!(
+                    "{} {}",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            columns,
+            [
+                "Name TEXT",
+                "Position TEXT",
+                "DOB TEXT",
+                "Nationality TEXT",
+                "Kit Number INTEGER"
+            ]
+        );
+        let n: i64 = conn.query_row(
+            r#"SELECT count(*) FROM juventus WHERE "Kit Number" > 33"#,
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(n, 2);
+        let index: String = conn.query_row(
+            "SELECT name FROM sqlite_master WHERE type = 'index'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(index, "idx_juventus_Nationality");
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_load_keeps_existing_table() -> Result<()> {
+        let output = TempPath::new(".db");
+        let read = |input| read_records(input, InputFormat::Csv, &Default::default(), "");
+        let records = read("assets/juventus.csv")?;
+        let types = vec![None; records.headers.len()];
+        write_table(&output, "players", records, &types, &[], false)?;
+
+        // the third line is malformed, as read from stdin the rows are
+        // inserted before it's reached
+        let records = read("fixtures/players_malformed.csv")?;
+        let types = vec![None; records.headers.len()];
+        let indexes = vec!["Name".to_string()];
+        assert!(write_table(&output, "players", records, &types, &indexes, true).is_err());
+
+        let conn = Connection::open(&output)?;
+        let n: i64 = conn.query_row("SELECT count(*) FROM players", [], |row| row.get(0))?;
+        assert_eq!(n, 27);
+        let columns: i64 = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('players')",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(columns, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_column_types() -> Result<()> {
+        let rows = vec![
+            Ok(serde_json::json!({"a": "1", "b": "", "c": "x", "d": 1.5})),
+            Ok(serde_json::json!({"a": "2.5", "b": null, "c": "1", "d": 2})),
+        ];
+        let records = Records::new(
+            vec!["a".into(), "b".into(), "c".into(), "d".into()],
+            Box::new(rows.into_iter()),
+        );
+        assert_eq!(
+            infer_column_types(records)?,
+            [
+                Some(CellType::Float),
+                None,
+                Some(CellType::String),
+                Some(CellType::Float)
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+use std::{borrow::Cow, cmp::Ordering, collections::HashSet};
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::cli::{CsvTransformOpts, SortKey};
+
+use super::{csv_filter::Filter, csv_reader::Records};
+
+/// Filter, dedupe, sort, project and rename the records in that order. Every
+/// option refers to the original column names, `--rename` is applied last.
+///
+/// Everything is lazy except `--sort-by`, which has to see all the rows.
+pub fn transform_records(records: Records, opts: &CsvTransformOpts) -> Result<Records> {
+    let Records {
+        headers,
+        rows,
+        summary,
+    } = records;
+
+    let filter = opts.filter.as_deref().map(Filter::parse).transpose()?;
+    let sort_columns = opts.sort_by.iter().map(|k| k.column.as_str());
+    let filter_columns = filter.iter().flat_map(|f| f.columns());
+    if let Some(name) = sort_columns
+        .chain(opts.unique_by.iter().map(String::as_str))
+        .chain(filter_columns)
+        .find(|name| !headers.iter().any(|h| h == name))
+    {
+        anyhow::bail!("Column not found: {}", name);
+    }
+    let columns = match &opts.select {
+        Some(select) => {
+            if let Some(name) = select.iter().find(|name| !headers.contains(name)) {
+                anyhow::bail!("Selected column not found: {}", name);
+            }
+            select.clone()
+        }
+        None => headers,
+    };
+    if let Some((name, _)) = opts.rename.iter().find(|(from, _)| !columns.contains(from)) {
+        anyhow::bail!("Renamed column not found: {}", name);
+    }
+    let renamed: Vec<String> = columns
+        .iter()
+        .map(|h| {
+            opts.rename
+                .iter()
+                .find(|(from, _)| from == h)
+                .map(|(_, to)| to.clone())
+                .unwrap_or_else(|| h.clone())
+        })
+        .collect();
+    // a repeated name would overwrite the other column in every row
+    let mut seen = HashSet::new();
+    if let Some(name) = renamed.iter().find(|name| !seen.insert(*name)) {
+        anyhow::bail!("Duplicate output column: {}", name);
+    }
+
+    let rows = rows.filter(move |row| match (row, &filter) {
+        (Ok(row), Some(filter)) => filter.matches(row),
+        _ => true,
+    });
+    let rows: Box<dyn Iterator<Item = Result<Value>>> = if opts.unique_by.is_empty() {
+        Box::new(rows)
+    } else {
+        let unique_by = opts.unique_by.clone();
+        let mut seen = HashSet::new();
+        Box::new(rows.filter(move |row| match row {
+            Ok(row) => seen.insert(row_key(row, &unique_by)),
+            Err(_) => true,
+        }))
+    };
+    let rows: Box<dyn Iterator<Item = Result<Value>>> = if opts.sort_by.is_empty() {
+        rows
+    } else {
+        let mut sorted = rows.collect::<Result<Vec<_>>>()?;
+        sorted.sort_by(|a, b| compare_rows(a, b, &opts.sort_by));
+        Box::new(sorted.into_iter().map(Ok))
+    };
+
+    let out_headers = renamed.clone();
+    let rows = rows.map(move |row| {
+        let row = row?;
+        let projected = columns
+            .iter()
+            .zip(&renamed)
+            .map(|(from, to)| (to.clone(), row.get(from).cloned().unwrap_or(Value::Null)))
+            .collect::<Map<_, _>>();
+        Ok(Value::Object(projected))
+    });
+    Ok(Records {
+        headers: out_headers,
+        rows: Box::new(rows),
+        summary,
+    })
+}
+
+/// The cells of the given columns, JSON-encoded so it can be hashed
+pub fn row_key(row: &Value, columns: &[String]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|c| row.get(c).unwrap_or(&Value::Null).to_string())
+        .collect()
+}
+
+/// Compare by each sort key in turn, nulls always sort last
+fn compare_rows(a: &Value, b: &Value, keys: &[SortKey]) -> Ordering {
+    for key in keys {
+        let l = a.get(&key.column).unwrap_or(&Value::Null);
+        let r = b.get(&key.column).unwrap_or(&Value::Null);
+        let ordering = match (l.is_null(), r.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => return Ordering::Greater,
+            (false, true) => return Ordering::Less,
+            _ => {
+                let ordering = compare_cells(l, r);
+                if key.desc {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// A total order over non-null cells, so columns of mixed types sort the same
+/// whatever the input order: numbers, numeric strings included, come first,
+/// then booleans, then text.
+fn compare_cells(l: &Value, r: &Value) -> Ordering {
+    match (sort_number(l), sort_number(r)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => match (l, r) {
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Bool(_), _) => Ordering::Less,
+            (_, Value::Bool(_)) => Ordering::Greater,
+            _ => sort_text(l).cmp(&sort_text(r)),
+        },
+    }
+}
+
+fn sort_number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        // "NaN" parses but can't be ordered among the numbers
+        Value::String(s) => s.trim().parse().ok().filter(|n: &f64| !n.is_nan()),
+        _ => None,
+    }
+}
+
+fn sort_text(v: &Value) -> Cow<'_, str> {
+    match v {
+        Value::String(s) => Cow::Borrowed(s),
+        v => Cow::Owned(v.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::InputFormat;
+    use crate::process::csv_reader::read_records;
+
+    #[test]
+    fn test_transform_records() -> Result<()> {
+        let records = read_records(
+            "assets/juventus.csv",
+            InputFormat::Csv,
+            &Default::default(),
+            "records",
+        )?;
+        let opts = CsvTransformOpts {
+            select: Some(vec!["Name".into(), "Kit Number".into()]),
+            rename: vec![("Kit Number".into(), "kit".into())],
+            filter: Some(r#"Position == "Centre-Forward" && Kit Number > 17"#.into()),
+            ..Default::default()
+        };
+        let records = transform_records(records, &opts)?;
+        assert_eq!(records.headers, vec!["Name", "kit"]);
+        let rows = records.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            rows,
+            vec![serde_json::json!({"Name": "Gonzalo Higuaín", "kit": "21"})]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_unknown_column() -> Result<()> {
+        let records = || {
+            read_records(
+                "assets/juventus.csv",
+                InputFormat::Csv,
+                &Default::default(),
+                "records",
+            )
+        };
+        let opts = CsvTransformOpts {
+            select: Some(vec!["Nope".into()]),
+            ..Default::default()
+        };
+        assert!(transform_records(records()?, &opts).is_err());
+        let opts = CsvTransformOpts {
+            rename: vec![("Nope".into(), "x".into())],
+            ..Default::default()
+        };
+        assert!(transform_records(records()?, &opts).is_err());
+        let opts = CsvTransformOpts {
+            filter: Some(r#"Positon == "Forward""#.into()),
+            ..Default::default()
+        };
+        let err = transform_records(records()?, &opts).err().unwrap();
+        assert_eq!(err.to_string(), "Column not found: Positon");
+
+        let opts = CsvTransformOpts {
+            rename: vec![("Name".into(), "Position".into())],
+            ..Default::default()
+        };
+        let err = transform_records(records()?, &opts).err().unwrap();
+        assert_eq!(err.to_string(), "Duplicate output column: Position");
+        let opts = CsvTransformOpts {
+            select: Some(vec!["Name".into(), "Name".into()]),
+            ..Default::default()
+        };
+        let err = transform_records(records()?, &opts).err().unwrap();
+        assert_eq!(err.to_string(), "Duplicate output column: Name");
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_sort_and_unique() -> Result<()> {
+        let records = read_records(
+            "assets/juventus.csv",
+            InputFormat::Csv,
+            &Default::default(),
+            "records",
+        )?;
+        let opts = CsvTransformOpts {
+            select: Some(vec!["Nationality".into(), "Kit Number".into()]),
+            unique_by: vec!["Nationality".into()],
+            sort_by: vec![SortKey {
+                column: "Kit Number".into(),
+                desc: true,
+            }],
+            ..Default::default()
+        };
+        let rows = transform_records(records, &opts)?
+            .rows
+            .collect::<Result<Vec<_>>>()?;
+        // dedupe keeps the first row per nationality, numeric strings sort as numbers
+        assert_eq!(rows.len(), 14);
+        assert_eq!(
+            rows[0],
+            serde_json::json!({"Nationality": "Italy", "Kit Number": "37"})
+        );
+        assert_eq!(
+            rows[13],
+            serde_json::json!({"Nationality": "Poland", "Kit Number": "1"})
+        );
+        let kits: Vec<f64> = rows
+            .iter()
+            .map(|r| r["Kit Number"].as_str().unwrap().parse().unwrap())
+            .collect();
+        assert!(kits.windows(2).all(|w| w[0] >= w[1]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_mixed_types() -> Result<()> {
+        let sort = |values: &[Value]| -> Result<Vec<Value>> {
+            let rows = values
+                .iter()
+                .map(|v| Ok(serde_json::json!({ "v": v })))
+                .collect::<Vec<_>>();
+            let records = Records::new(vec!["v".into()], Box::new(rows.into_iter()));
+            let opts = CsvTransformOpts {
+                sort_by: vec![SortKey {
+                    column: "v".into(),
+                    desc: false,
+                }],
+                ..Default::default()
+            };
+            transform_records(records, &opts)?
+                .rows
+                .map(|row| Ok(row?["v"].clone()))
+                .collect()
+        };
+        let expected = ["9", "10", "5x"];
+        assert_eq!(sort(&["9".into(), "5x".into(), "10".into()])?, expected);
+        assert_eq!(sort(&["10".into(), "5x".into(), "9".into()])?, expected);
+        // numbers first, then booleans, then text, nulls last
+        let sorted = sort(&[
+            Value::Null,
+            "b".into(),
+            true.into(),
+            2.into(),
+            "NaN".into(),
+            "1.5".into(),
+        ])?;
+        assert_eq!(
+            sorted,
+            [
+                Value::from("1.5"),
+                2.into(),
+                true.into(),
+                "NaN".into(),
+                "b".into(),
+                Value::Null
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs,
+};
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+/// Per-column cell types, loaded from a YAML/JSON file like `{"Kit Number": integer}`
+pub type Schema = IndexMap<String, CellType>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    Datetime,
+}
+
+pub fn load_schema(path: &str) -> Result<Schema> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_yaml::from_str(&content)?)
+}
+
+impl CellType {
+    /// Guess the narrowest type of a cell, `None` for an empty cell
+    pub fn infer(s: &str) -> Option<Self> {
+        if s.is_empty() {
+            return None;
+        }
+        let ty = [
+            CellType::Integer,
+            CellType::Float,
+            CellType::Boolean,
+            CellType::Date,
+            CellType::Datetime,
+        ]
+        .into_iter()
+        .find(|ty| ty.parse(s).is_some())
+        .unwrap_or(CellType::String);
+        Some(ty)
+    }
+
+    /// The narrowest type that fits cells of both types
+    pub fn unify(self, other: Self) -> Self {
+        use CellType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Integer, Float) | (Float, Integer) => Float,
+            (Date, Datetime) | (Datetime, Date) => Datetime,
+            _ => String,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, CellType::Integer | CellType::Float)
+    }
+
+    /// Parse a non-empty cell as this type, dates are kept as strings once validated
+    pub fn parse(&self, s: &str) -> Option<Value> {
+        match self {
+            CellType::String => Some(Value::String(s.to_string())),
+            CellType::Integer => {
+                // keep zero-padded codes like `007` as they are
+                let digits = s.strip_prefix('-').unwrap_or(s);
+                if !is_integer_literal(s) || (digits.len() > 1 && digits.starts_with('0')) {
+                    return None;
+                }
+                s.parse::<i64>().ok().map(Value::from)
+            }
+            CellType::Float => {
+                // integers too large for i64 are IDs rather than quantities,
+                // a float would silently round them
+                if is_integer_literal(s) && s.parse::<i64>().is_err() {
+                    return None;
+                }
+                // rule out `inf`, `NaN` and friends which f64 happily parses
+                if !s.bytes().any(|b| b.is_ascii_digit())
+                    || !s.bytes().all(|b| {
+                        b.is_ascii_digit() || matches!(b, b'.' | b'-' | b'+' | b'e' | b'E')
+                    })
+                {
+                    return None;
+                }
+                let int_part = s.strip_prefix('-').unwrap_or(s);
+                let int_part = int_part.split(['.', 'e', 'E']).next().unwrap_or_default();
+                if int_part.len() > 1 && int_part.starts_with('0') {
+                    return None;
+                }
+                let f = s.parse::<f64>().ok()?;
+                Number::from_f64(f).map(Value::Number)
+            }
+            CellType::Boolean => match s {
+                "true" | "True" | "TRUE" => Some(Value::Bool(true)),
+                "false" | "False" | "FALSE" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            CellType::Date => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .map(|_| Value::String(s.to_string())),
+            CellType::Datetime => {
+                let valid = DateTime::parse_from_rfc3339(s).is_ok()
+                    || NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+                    || NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").is_ok();
+                valid.then(|| Value::String(s.to_string()))
+            }
+        }
+    }
+}
+
+impl Display for CellType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            CellType::String => "string",
+            CellType::Integer => "integer",
+            CellType::Float => "float",
+            CellType::Boolean => "boolean",
+            CellType::Date => "date",
+            CellType::Datetime => "datetime",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An optional minus sign followed by digits only
+fn is_integer_literal(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Convert a cell with the inferred type, empty cells become null
+pub fn infer_value(s: &str) -> Value {
+    match CellType::infer(s) {
+        Some(ty) => ty.parse(s).unwrap_or_else(|| Value::String(s.to_string())),
+        None => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_cell_type() {
+        assert_eq!(CellType::infer(""), None);
+        assert_eq!(CellType::infer("10"), Some(CellType::Integer));
+        assert_eq!(CellType::infer("-3"), Some(CellType::Integer));
+        assert_eq!(CellType::infer("007"), Some(CellType::String));
+        assert_eq!(CellType::infer("1.5e3"), Some(CellType::Float));
+        // too large for i64, a float would round the last digits
+        assert_eq!(
+            CellType::infer("12345678901234567890"),
+            Some(CellType::String)
+        );
+        assert_eq!(
+            CellType::infer("-9223372036854775808"),
+            Some(CellType::Integer)
+        );
+        assert_eq!(CellType::infer("1e19"), Some(CellType::Float));
+        assert_eq!(CellType::infer("NaN"), Some(CellType::String));
+        assert_eq!(CellType::infer("inf"), Some(CellType::String));
+        assert_eq!(CellType::infer("TRUE"), Some(CellType::Boolean));
+        assert_eq!(CellType::infer("2024-02-29"), Some(CellType::Date));
+        assert_eq!(CellType::infer("2023-02-29"), Some(CellType::String));
+        assert_eq!(
+            CellType::infer("2024-05-01T08:30:00Z"),
+            Some(CellType::Datetime)
+        );
+        assert_eq!(CellType::infer("Apr 18, 1990 (29)"), Some(CellType::String));
+    }
+
+    #[test]
+    fn test_unify_cell_type() {
+        assert_eq!(CellType::Integer.unify(CellType::Float), CellType::Float);
+        assert_eq!(CellType::Date.unify(CellType::Datetime), CellType::Datetime);
+        assert_eq!(CellType::Integer.unify(CellType::Boolean), CellType::String);
+        assert_eq!(CellType::Date.unify(CellType::Date), CellType::Date);
+    }
+
+    #[test]
+    fn test_infer_value() {
+        assert_eq!(infer_value(""), Value::Null);
+        assert_eq!(infer_value("10"), Value::from(10));
+        assert_eq!(infer_value("0.5"), Value::from(0.5));
+        assert_eq!(infer_value("false"), Value::Bool(false));
+        assert_eq!(infer_value("2024-02-29"), Value::from("2024-02-29"));
+        assert_eq!(
+            infer_value("12345678901234567890"),
+            Value::from("12345678901234567890")
+        );
+    }
+}
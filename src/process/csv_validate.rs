@@ -0,0 +1,119 @@
+use std::fs;
+
+use anyhow::Result;
+use jsonschema::{error::ValidationErrorKind, JSONSchema};
+use serde_json::Value;
+
+/// A JSON Schema every output row is checked against
+pub struct RowValidator {
+    schema: JSONSchema,
+}
+
+/// Where an invalid row comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowRef {
+    /// Line of the source file
+    Line(u64),
+    /// 1-based index in the output, when rows can't be traced back to a line
+    Output(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub row: RowRef,
+    /// Dotted path of the offending cell, empty when the row as a whole is invalid
+    pub column: String,
+    pub message: String,
+}
+
+impl RowValidator {
+    pub fn load(path: &str) -> Result<Self> {
+        let schema: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let schema = JSONSchema::compile(&schema)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON Schema {}: {}", path, e))?;
+        Ok(Self { schema })
+    }
+
+    /// Every violation of the row, empty when it's valid
+    pub fn check(&self, row: RowRef, value: &Value) -> Vec<Violation> {
+        let Err(errors) = self.schema.validate(value) else {
+            return Vec::new();
+        };
+        errors
+            .map(|e| {
+                let mut path = e.instance_path.clone().into_vec();
+                // a missing column is reported on the row, name it anyway
+                if let ValidationErrorKind::Required {
+                    property: Value::String(property),
+                } = &e.kind
+                {
+                    path.push(property.clone());
+                }
+                Violation {
+                    row,
+                    column: path.join("."),
+                    message: e.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for RowRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RowRef::Line(line) => write!(f, "Line {}", line),
+            RowRef::Output(row) => write!(f, "Output row {}", row),
+        }
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.column.is_empty() {
+            write!(f, "{}: {}", self.row, self.message)
+        } else {
+            write!(f, "{}, column {}: {}", self.row, self.column, self.message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_row_validator() -> Result<()> {
+        let validator = RowValidator::load("fixtures/players.schema.json")?;
+        let row = json!({
+            "Name": "Wojciech Szczesny",
+            "Position": "Goalkeeper",
+            "DOB": "Apr 18, 1990 (29)",
+            "Nationality": "Poland",
+            "Kit Number": 1
+        });
+        assert!(validator.check(RowRef::Line(2), &row).is_empty());
+
+        let row = json!({
+            "Name": "Mattia Perin",
+            "Position": "Goalkeeper",
+            "Nationality": null,
+            "Kit Number": 777
+        });
+        let mut violations = validator.check(RowRef::Line(3), &row);
+        violations.sort_by(|a, b| a.column.cmp(&b.column));
+        let columns: Vec<&str> = violations.iter().map(|v| v.column.as_str()).collect();
+        assert_eq!(columns, ["DOB", "Kit Number", "Nationality"]);
+        assert_eq!(
+            violations[1].to_string(),
+            "Line 3, column Kit Number: 777 is greater than the maximum of 255"
+        );
+        let violations = validator.check(RowRef::Output(5), &json!({}));
+        assert!(violations[0]
+            .to_string()
+            .starts_with("Output row 5, column "));
+        assert!(RowValidator::load("fixtures/players_schema.yml").is_err());
+        Ok(())
+    }
+}
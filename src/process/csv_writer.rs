@@ -0,0 +1,356 @@
+use std::io::Write;
+
+use anyhow::Result;
+use csv::{QuoteStyle, WriterBuilder};
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::{CsvQuoteStyle, CsvWriterOpts, OutputFormat},
+    get_writer,
+};
+
+use super::{
+    csv_markup::{HtmlWriter, LatexWriter, MarkdownWriter},
+    csv_nest::nest_records,
+    csv_reader::Records,
+    csv_show::render_table,
+};
+
+/// An incremental serializer, records are written one at a time so the
+/// memory stays bounded no matter how large the input is.
+pub trait RecordWriter {
+    fn write(&mut self, record: &Value) -> Result<()>;
+
+    /// Write the trailer (if any) and flush the output
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+struct JsonWriter {
+    out: Box<dyn Write>,
+    count: usize,
+}
+
+struct YamlWriter {
+    out: Box<dyn Write>,
+    count: usize,
+}
+
+struct TomlWriter {
+    out: Box<dyn Write>,
+    key: String,
+    count: usize,
+}
+
+struct NdjsonWriter {
+    out: Box<dyn Write>,
+}
+
+struct TableWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    rows: Vec<Value>,
+    max_width: usize,
+}
+
+pub struct CsvWriter {
+    writer: csv::Writer<Box<dyn Write>>,
+    headers: Vec<String>,
+}
+
+/// Serialize the records to the output file, `-` for stdout
+pub fn write_records(
+    records: Records,
+    output: &str,
+    format: OutputFormat,
+    opts: &CsvWriterOpts,
+) -> Result<()> {
+    let records = if opts.nest {
+        nest_records(records)?
+    } else {
+        records
+    };
+    let mut writer = record_writer(format, &records.headers, opts, get_writer(output)?)?;
+    for record in records.rows {
+        writer.write(&record?)?;
+    }
+    writer.finish()
+}
+
+pub fn record_writer(
+    format: OutputFormat,
+    headers: &[String],
+    opts: &CsvWriterOpts,
+    out: Box<dyn Write>,
+) -> Result<Box<dyn RecordWriter>> {
+    let writer: Box<dyn RecordWriter> = match format {
+        OutputFormat::Json => Box::new(JsonWriter { out, count: 0 }),
+        OutputFormat::Yaml => Box::new(YamlWriter { out, count: 0 }),
+        OutputFormat::Toml => Box::new(TomlWriter {
+            out,
+            key: opts.toml_key.clone(),
+            count: 0,
+        }),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter { out }),
+        OutputFormat::Csv => {
+            let mut writer = csv_rows_writer(headers, opts, out);
+            writer.writer.write_record(headers)?;
+            Box::new(writer)
+        }
+        OutputFormat::Table => Box::new(TableWriter {
+            out,
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+            max_width: opts.max_width.max(2),
+        }),
+        OutputFormat::Markdown => Box::new(MarkdownWriter::new(headers, out)),
+        OutputFormat::Html => Box::new(HtmlWriter::new(headers, opts.standalone, out)?),
+        OutputFormat::Latex => Box::new(LatexWriter::new(headers, out)),
+    };
+    Ok(writer)
+}
+
+/// A CSV writer without the header row, to append to a file that has one
+pub fn csv_rows_writer(headers: &[String], opts: &CsvWriterOpts, out: Box<dyn Write>) -> CsvWriter {
+    let writer = WriterBuilder::new()
+        .delimiter(opts.output_delimiter)
+        .quote_style(opts.quote_style.into())
+        .from_writer(out);
+    CsvWriter {
+        writer,
+        headers: headers.to_vec(),
+    }
+}
+
+impl RecordWriter for JsonWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        // same layout as `to_string_pretty` on the whole array
+        let prefix = if self.count == 0 { "[\n" } else { ",\n" };
+        self.out.write_all(prefix.as_bytes())?;
+        let content = serde_json::to_string_pretty(record)?;
+        for (i, line) in content.lines().enumerate() {
+            if i > 0 {
+                self.out.write_all(b"\n")?;
+            }
+            write!(self.out, "  {}", line)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let trailer = if self.count == 0 { "[]" } else { "\n]" };
+        self.out.write_all(trailer.as_bytes())?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for YamlWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        // a one-item sequence per record, together they form a single sequence
+        let content = serde_yaml::to_string(&[record])?;
+        self.out.write_all(content.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.count == 0 {
+            self.out.write_all(b"[]\n")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for TomlWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        // every record is one `[[key]]` table of the array
+        let mut table = Map::new();
+        table.insert(self.key.clone(), Value::Array(vec![strip_nulls(record)]));
+        if self.count > 0 {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.write_all(toml::to_string(&table)?.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.count == 0 {
+            writeln!(self.out, "{} = []", self.key)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for NdjsonWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.out, record)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for CsvWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let cells = self
+            .headers
+            .iter()
+            .map(|h| record.get(h).map(value_to_cell).unwrap_or_default());
+        self.writer.write_record(cells)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for TableWriter {
+    // column widths depend on every row, so the table is rendered at the end
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.rows.push(record.clone());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let table = render_table(&self.headers, &self.rows, self.max_width);
+        self.out.write_all(table.as_bytes())?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// TOML has no null, so empty cells are left out of the table
+fn strip_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(obj) => obj
+            .iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k.clone(), strip_nulls(v)))
+            .collect(),
+        Value::Array(items) => items.iter().map(strip_nulls).collect(),
+        v => v.clone(),
+    }
+}
+
+/// Render a JSON value as a single CSV cell, strings are written unquoted
+pub fn value_to_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+impl From<CsvQuoteStyle> for QuoteStyle {
+    fn from(style: CsvQuoteStyle) -> Self {
+        match style {
+            CsvQuoteStyle::Always => QuoteStyle::Always,
+            CsvQuoteStyle::Necessary => QuoteStyle::Necessary,
+            CsvQuoteStyle::NonNumeric => QuoteStyle::NonNumeric,
+            CsvQuoteStyle::Never => QuoteStyle::Never,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, io, rc::Rc};
+
+    /// A writer that can be read back after the record writer consumed it
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn render(format: OutputFormat, rows: &[Value]) -> Result<String> {
+        let buf = SharedBuf::default();
+        let headers = vec!["name".to_string(), "kit".to_string()];
+        let mut writer =
+            record_writer(format, &headers, &Default::default(), Box::new(buf.clone()))?;
+        for row in rows {
+            writer.write(row)?;
+        }
+        writer.finish()?;
+        let content = buf.0.borrow().clone();
+        Ok(String::from_utf8(content)?)
+    }
+
+    #[test]
+    fn test_streaming_matches_whole_document() -> Result<()> {
+        let rows = vec![
+            serde_json::json!({"name": "Dybala", "kit": 10}),
+            serde_json::json!({"name": "Buffon", "kit": null}),
+        ];
+        assert_eq!(
+            render(OutputFormat::Json, &rows)?,
+            serde_json::to_string_pretty(&rows)?
+        );
+        assert_eq!(
+            render(OutputFormat::Yaml, &rows)?,
+            serde_yaml::to_string(&rows)?
+        );
+        let content = render(OutputFormat::Toml, &rows)?;
+        let table: toml::Table = content.parse()?;
+        assert_eq!(table["records"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            render(OutputFormat::Csv, &rows)?,
+            "name,kit\nDybala,10\nBuffon,\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_markup_formats() -> Result<()> {
+        let rows = vec![
+            serde_json::json!({"name": "Dybala", "kit": 10}),
+            serde_json::json!({"name": "A|B <C>", "kit": null}),
+        ];
+        assert_eq!(
+            render(OutputFormat::Markdown, &rows)?,
+            "| name | kit |\n| --- | ---: |\n| Dybala | 10 |\n| A\\|B &lt;C> |  |\n"
+        );
+        let html = render(OutputFormat::Html, &rows)?;
+        assert!(html.starts_with("<table>\n  <thead>\n    <tr>\n      <th>name</th>"));
+        assert!(html.contains("<td style=\"text-align: right\">10</td>"));
+        assert!(html.contains("<td>A|B &lt;C&gt;</td>"));
+        assert!(html.ends_with("  </tbody>\n</table>\n"));
+        assert_eq!(
+            render(OutputFormat::Latex, &rows)?,
+            "\\begin{tabular}{lr}\n\\hline\nname & kit \\\\\n\\hline\n\
+             Dybala & 10 \\\\\nA|B <C> &  \\\\\n\\hline\n\\end{tabular}\n"
+        );
+        assert_eq!(
+            render(OutputFormat::Markdown, &[])?,
+            "| name | kit |\n| --- | --- |\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_empty_input() -> Result<()> {
+        assert_eq!(render(OutputFormat::Json, &[])?, "[]");
+        assert_eq!(render(OutputFormat::Yaml, &[])?, "[]\n");
+        let table: toml::Table = render(OutputFormat::Toml, &[])?.parse()?;
+        assert!(table["records"].as_array().unwrap().is_empty());
+        Ok(())
+    }
+}
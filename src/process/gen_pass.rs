@@ -1,39 +1,172 @@
 use anyhow::Result;
-use rand::seq::SliceRandom;
-
-const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
-const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
-const NUMBER: &[u8] = b"123456789";
-const SYMBOL: &[u8] = b"!@#$%^&*_";
-
-pub fn process_genpass(
-    length: u8,
-    upper: bool,
-    lower: bool,
-    number: bool,
-    symbol: bool,
-) -> Result<String> {
-    let mut rng = rand::thread_rng();
-    let mut password = Vec::new();
-    let mut chars = Vec::new();
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+use zxcvbn::{zxcvbn, Entropy};
+
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const NUMBER: &str = "0123456789";
+const SYMBOL: &str = "!@#$%^&*_";
+/// Left out of the character classes unless `allow_ambiguous` is set
+const AMBIGUOUS: &str = "0OIl";
+
+const HEX: &str = "0123456789abcdef";
+const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// One word per line, every word is equally likely to be picked
+const WORDLIST: &str = include_str!("../../assets/wordlist.txt");
+
+/// Give up on `--min-score` after this many weak passwords in a row
+const MAX_ATTEMPTS: usize = 1000;
+
+/// zxcvbn's estimate of a password, with the feedback on how to improve it
+#[derive(Debug, Clone, Serialize)]
+pub struct Strength {
+    pub score: u8,
+    pub guesses_log10: f64,
+    pub crack_times: CrackTimes,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// Human-readable time to crack the password in a few attack scenarios
+#[derive(Debug, Clone, Serialize)]
+pub struct CrackTimes {
+    pub online_throttling_100_per_hour: String,
+    pub online_no_throttling_10_per_second: String,
+    pub offline_slow_hashing_1e4_per_second: String,
+    pub offline_fast_hashing_1e10_per_second: String,
+}
+
+/// What a generated password must contain. Every enabled class has a minimum
+/// count, `None` leaves the class out of the password entirely. A `charset`
+/// replaces the classes, it's used as is without dropping ambiguous characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub length: u16,
+    pub upper: Option<u16>,
+    pub lower: Option<u16>,
+    pub number: Option<u16>,
+    pub symbol: Option<u16>,
+    pub charset: Option<String>,
+    /// Characters never used, whatever the classes or charset
+    pub exclude: String,
+    pub allow_ambiguous: bool,
+}
+
+/// An enabled class: its name, its characters without duplicates and its
+/// minimum count
+type Class = (&'static str, Vec<char>, u16);
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: 16,
+            upper: Some(1),
+            lower: Some(1),
+            number: Some(1),
+            symbol: Some(1),
+            charset: None,
+            exclude: String::new(),
+            allow_ambiguous: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// The enabled classes with their minimum counts
+    fn classes(&self) -> Vec<Class> {
+        if let Some(charset) = &self.charset {
+            return vec![("the charset", self.alphabet(charset, false), 0)];
+        }
+        [
+            ("uppercase letters", UPPER, self.upper),
+            ("lowercase letters", LOWER, self.lower),
+            ("numbers", NUMBER, self.number),
+            ("symbols", SYMBOL, self.symbol),
+        ]
+        .into_iter()
+        .filter_map(|(name, chars, min)| {
+            min.map(|min| (name, self.alphabet(chars, !self.allow_ambiguous), min))
+        })
+        .collect()
+    }
+
+    /// Sorted and deduplicated, so every character is equally likely
+    fn alphabet(&self, chars: &str, drop_ambiguous: bool) -> Vec<char> {
+        let mut alphabet: Vec<char> = chars
+            .chars()
+            .filter(|c| !self.exclude.contains(*c))
+            .filter(|c| !(drop_ambiguous && AMBIGUOUS.contains(*c)))
+            .collect();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        alphabet
+    }
 
-    if upper {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
+    pub fn validate(&self) -> Result<()> {
+        let classes = self.classes();
+        if classes.is_empty() {
+            anyhow::bail!("At least one character class must be enabled");
+        }
+        if let Some((name, _, _)) = classes.iter().find(|(_, chars, _)| chars.is_empty()) {
+            anyhow::bail!("No characters left in {} after the exclusions", name);
+        }
+        if self.length == 0 {
+            anyhow::bail!("Password length must be greater than 0");
+        }
+        let required: u32 = classes.iter().map(|(_, _, min)| *min as u32).sum();
+        if required > self.length as u32 {
+            anyhow::bail!(
+                "Password length {} is shorter than the {} characters the minimum counts require",
+                self.length,
+                required
+            );
+        }
+        Ok(())
     }
-    if lower {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+
+    /// Approximate entropy in bits, every character is counted as drawn from
+    /// all the enabled classes
+    pub fn entropy(&self) -> f64 {
+        let size: usize = self.classes().iter().map(|(_, chars, _)| chars.len()).sum();
+        if size == 0 {
+            return 0.0;
+        }
+        self.length as f64 * (size as f64).log2()
     }
-    if number {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+}
+
+/// The alphabet of a preset (`hex`, `base58`, `alnum`, `ascii-printable`),
+/// anything else is the alphabet itself
+pub fn charset_alphabet(charset: &str) -> String {
+    match charset {
+        "hex" => HEX.to_string(),
+        "base58" => BASE58.to_string(),
+        "alnum" => format!("{}{}{}", NUMBER, UPPER, LOWER),
+        // without the space
+        "ascii-printable" => ('!'..='~').collect(),
+        alphabet => alphabet.to_string(),
     }
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+}
+
+pub fn process_genpass(policy: &PasswordPolicy) -> Result<String> {
+    policy.validate()?;
+    let mut rng = rand::thread_rng();
+    let classes = policy.classes();
+    // the classes are disjoint, so the union stays uniform
+    let chars: Vec<char> = classes
+        .iter()
+        .flat_map(|(_, class, _)| class.iter().copied())
+        .collect();
+    let mut password = Vec::with_capacity(policy.length as usize);
+
+    for (_, class, min) in &classes {
+        for _ in 0..*min {
+            password.push(*class.choose(&mut rng).expect("classes won't be empty"));
+        }
     }
-    for _ in 0..(length - password.len() as u8) {
+    while password.len() < policy.length as usize {
         let c = chars
             .choose(&mut rng)
             .expect("chars won't be empty in this context");
@@ -42,7 +175,342 @@ pub fn process_genpass(
 
     password.shuffle(&mut rng);
 
-    let password = String::from_utf8(password)?;
+    Ok(password.into_iter().collect())
+}
+
+/// Call `generate` until zxcvbn scores the password at least `min_score` (0-4)
+pub fn generate_with_min_score(
+    min_score: u8,
+    mut generate: impl FnMut() -> Result<String>,
+) -> Result<(String, Strength)> {
+    for _ in 0..MAX_ATTEMPTS {
+        let password = generate()?;
+        let strength = Strength::estimate(&password)?;
+        if strength.score >= min_score {
+            return Ok((password, strength));
+        }
+    }
+    anyhow::bail!(
+        "No password reached score {} in {} attempts, make it longer or use more character classes",
+        min_score,
+        MAX_ATTEMPTS
+    )
+}
+
+impl Strength {
+    pub fn estimate(password: &str) -> Result<Self> {
+        Ok(zxcvbn(password, &[])?.into())
+    }
+}
+
+impl From<Entropy> for Strength {
+    fn from(entropy: Entropy) -> Self {
+        let times = entropy.crack_times();
+        let (warning, suggestions) = match entropy.feedback() {
+            Some(feedback) => (
+                feedback.warning().map(|w| w.to_string()),
+                feedback
+                    .suggestions()
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            None => (None, Vec::new()),
+        };
+        Self {
+            score: entropy.score(),
+            guesses_log10: entropy.guesses_log10(),
+            crack_times: CrackTimes {
+                online_throttling_100_per_hour: times.online_throttling_100_per_hour().to_string(),
+                online_no_throttling_10_per_second: times
+                    .online_no_throttling_10_per_second()
+                    .to_string(),
+                offline_slow_hashing_1e4_per_second: times
+                    .offline_slow_hashing_1e4_per_second()
+                    .to_string(),
+                offline_fast_hashing_1e10_per_second: times
+                    .offline_fast_hashing_1e10_per_second()
+                    .to_string(),
+            },
+            warning,
+            suggestions,
+        }
+    }
+}
+
+/// Generate a diceware-style passphrase of `words` random words. Capitalizing
+/// every word doesn't add entropy, `digit` appends a random digit to a random
+/// word. Words and digits containing an `exclude` character are never picked.
+pub fn process_genphrase(
+    words: u16,
+    separator: &str,
+    capitalize: bool,
+    digit: bool,
+    exclude: &str,
+) -> Result<String> {
+    if words == 0 {
+        anyhow::bail!("A passphrase needs at least one word");
+    }
+    if let Some(c) = separator.chars().find(|c| exclude.contains(*c)) {
+        anyhow::bail!("The separator contains the excluded character {:?}", c);
+    }
+    let wordlist = phrase_words(capitalize, exclude);
+    if wordlist.is_empty() {
+        anyhow::bail!("No words left after the exclusions");
+    }
+    let digits = phrase_digits(exclude);
+    if digit && digits.is_empty() {
+        anyhow::bail!("No digits left after the exclusions");
+    }
+    let mut rng = rand::thread_rng();
+    let mut phrase: Vec<String> = (0..words)
+        .map(|_| {
+            wordlist
+                .choose(&mut rng)
+                .expect("wordlist won't be empty")
+                .clone()
+        })
+        .collect();
+    if digit {
+        let i = rng.gen_range(0..phrase.len());
+        phrase[i].push(*digits.choose(&mut rng).expect("digits won't be empty"));
+    }
+    Ok(phrase.join(separator))
+}
+
+/// Entropy of `process_genphrase` in bits: every word is one of the words
+/// left after the exclusions, the digit is one of the digits left on one of
+/// the words
+pub fn passphrase_entropy(words: u16, capitalize: bool, digit: bool, exclude: &str) -> f64 {
+    let size = phrase_words(capitalize, exclude).len();
+    if size == 0 {
+        return 0.0;
+    }
+    let mut bits = words as f64 * (size as f64).log2();
+    let digits = phrase_digits(exclude).len();
+    if digit && digits > 0 {
+        bits += (digits as f64).log2() + (words as f64).log2();
+    }
+    bits
+}
+
+/// The words of the wordlist as they'd appear in a passphrase, without the
+/// ones containing an excluded character
+fn phrase_words(capitalize: bool, exclude: &str) -> Vec<String> {
+    WORDLIST
+        .lines()
+        .map(|word| {
+            if capitalize {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                word.to_string()
+            }
+        })
+        .filter(|word| !word.contains(|c| exclude.contains(c)))
+        .collect()
+}
+
+fn phrase_digits(exclude: &str) -> Vec<char> {
+    ('0'..='9').filter(|c| !exclude.contains(*c)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_genphrase() -> Result<()> {
+        let wordlist: Vec<&str> = WORDLIST.lines().collect();
+        let phrase = process_genphrase(6, "-", false, false, "")?;
+        let words: Vec<&str> = phrase.split('-').collect();
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| wordlist.contains(w)));
+
+        let phrase = process_genphrase(4, " ", true, true, "")?;
+        let words: Vec<&str> = phrase.split(' ').collect();
+        assert!(words
+            .iter()
+            .all(|w| w.starts_with(|c: char| c.is_ascii_uppercase())));
+        assert_eq!(phrase.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+
+        assert!(process_genphrase(0, "-", false, false, "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_genphrase_exclude() -> Result<()> {
+        for _ in 0..20 {
+            let phrase = process_genphrase(8, "-", true, true, "aA7")?;
+            assert!(!phrase.contains(['a', 'A', '7']));
+        }
+        let size = phrase_words(false, "a").len();
+        assert!(size < 2048);
+        let bits = passphrase_entropy(4, false, true, "a7");
+        assert!((bits - (4.0 * (size as f64).log2() + 9f64.log2() + 2.0)).abs() < 1e-9);
+        // only capitalized words start with an uppercase letter
+        assert_eq!(
+            phrase_words(false, "ABCDEFGHIJKLMNOPQRSTUVWXYZ").len(),
+            2048
+        );
+        assert!(process_genphrase(4, "-", false, false, "-").is_err());
+        assert!(process_genphrase(4, "-", false, false, "aeiouy").is_err());
+        assert!(process_genphrase(4, "-", false, true, "0123456789").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_with_min_score() -> Result<()> {
+        let mut candidates = vec!["password", "Tr0ub4dour&3-correct-horse"].into_iter();
+        let (password, strength) =
+            generate_with_min_score(3, || Ok(candidates.next().unwrap().to_string()))?;
+        assert_eq!(password, "Tr0ub4dour&3-correct-horse");
+        assert!(strength.score >= 3);
+        assert!(generate_with_min_score(4, || Ok("password".to_string())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_strength_feedback() -> Result<()> {
+        let strength = Strength::estimate("password123")?;
+        assert_eq!(strength.score, 0);
+        assert_eq!(
+            strength.crack_times.online_throttling_100_per_hour,
+            "5 hours"
+        );
+        assert!(strength.warning.is_some());
+        assert!(!strength.suggestions.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass() -> Result<()> {
+        let policy = PasswordPolicy {
+            length: 300,
+            upper: Some(5),
+            lower: None,
+            number: Some(20),
+            symbol: Some(0),
+            ..Default::default()
+        };
+        let password = process_genpass(&policy)?;
+        assert_eq!(password.len(), 300);
+        assert!(password.chars().filter(|c| UPPER.contains(*c)).count() >= 5);
+        assert!(password.chars().filter(|c| NUMBER.contains(*c)).count() >= 20);
+        assert!(!password.chars().any(|c| LOWER.contains(c)));
+        assert!(!password.chars().any(|c| AMBIGUOUS.contains(c)));
+
+        // the minimum counts fill the whole password
+        let policy = PasswordPolicy {
+            length: 4,
+            upper: Some(2),
+            lower: Some(2),
+            number: None,
+            symbol: None,
+            ..Default::default()
+        };
+        let password = process_genpass(&policy)?;
+        assert_eq!(password.chars().filter(|c| UPPER.contains(*c)).count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_policy() {
+        let policy = PasswordPolicy {
+            length: 3,
+            ..Default::default()
+        };
+        assert!(process_genpass(&policy).is_err());
+        let policy = PasswordPolicy {
+            length: 0,
+            upper: Some(0),
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+        let policy = PasswordPolicy {
+            length: 8,
+            upper: None,
+            lower: None,
+            number: None,
+            symbol: None,
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+    }
 
-    Ok(password)
+    #[test]
+    fn test_charset_and_exclude() -> Result<()> {
+        let policy = PasswordPolicy {
+            length: 64,
+            charset: Some(charset_alphabet("hex")),
+            exclude: "abc".to_string(),
+            ..Default::default()
+        };
+        let password = process_genpass(&policy)?;
+        assert_eq!(password.len(), 64);
+        assert!(password.chars().all(|c| "0123456789def".contains(c)));
+        assert!((policy.entropy() - 64.0 * 13f64.log2()).abs() < 1e-9);
+
+        // duplicates don't make a character more likely
+        let policy = PasswordPolicy {
+            length: 8,
+            charset: Some("ééab".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(policy.entropy(), 8.0 * 3f64.log2());
+        assert_eq!(process_genpass(&policy)?.chars().count(), 8);
+
+        assert_eq!(charset_alphabet("base58").len(), 58);
+        assert_eq!(charset_alphabet("alnum").len(), 62);
+        assert_eq!(charset_alphabet("ascii-printable").len(), 94);
+        assert_eq!(charset_alphabet("xyz"), "xyz");
+
+        let policy = PasswordPolicy {
+            charset: Some("ab".to_string()),
+            exclude: "ab".to_string(),
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+        let policy = PasswordPolicy {
+            exclude: SYMBOL.to_string(),
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_allow_ambiguous() {
+        let policy = PasswordPolicy {
+            allow_ambiguous: true,
+            ..Default::default()
+        };
+        assert!((policy.entropy() - 16.0 * 71f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_policy_entropy() {
+        let bits = PasswordPolicy::default().entropy();
+        assert!((bits - 16.0 * 67f64.log2()).abs() < 1e-9);
+        let policy = PasswordPolicy {
+            length: 10,
+            upper: None,
+            lower: None,
+            number: Some(1),
+            symbol: None,
+            ..Default::default()
+        };
+        assert!((policy.entropy() - 10.0 * 9f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_passphrase_entropy() {
+        assert_eq!(WORDLIST.lines().count(), 2048);
+        assert_eq!(passphrase_entropy(6, false, false, ""), 66.0);
+        let bits = passphrase_entropy(4, false, true, "");
+        assert!((bits - (44.0 + 10f64.log2() + 2.0)).abs() < 1e-9);
+    }
 }
@@ -1,5 +1,25 @@
 mod b64;
 mod csv_convert;
+mod csv_diff;
+mod csv_encoding;
+mod csv_filter;
+mod csv_group;
+mod csv_join;
+mod csv_markup;
+mod csv_merge;
+mod csv_nest;
+mod csv_query;
+mod csv_reader;
+mod csv_render;
+mod csv_show;
+mod csv_split;
+mod csv_sqlite;
+mod csv_stats;
+mod csv_to_sqlite;
+mod csv_transform;
+mod csv_types;
+mod csv_validate;
+mod csv_writer;
 mod gen_pass;
 mod http_serve;
 mod jwt;
@@ -8,7 +28,24 @@ mod text;
 pub use b64::process_decode;
 pub use b64::process_encode;
 pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
+pub use csv_diff::process_csv_diff;
+pub use csv_group::process_csv_group;
+pub use csv_join::process_csv_join;
+pub use csv_merge::process_csv_merge;
+pub use csv_nest::nest_records;
+pub use csv_query::process_csv_query;
+pub use csv_reader::{read_records, RejectSink};
+pub use csv_render::{process_csv_render, process_csv_render_rows};
+pub use csv_show::process_csv_show;
+pub use csv_split::{process_csv_split, SplitBy};
+pub use csv_stats::process_csv_stats;
+pub use csv_to_sqlite::process_csv_to_sqlite;
+pub use csv_transform::transform_records;
+pub use csv_writer::write_records;
+pub use gen_pass::{
+    charset_alphabet, generate_with_min_score, passphrase_entropy, process_genpass,
+    process_genphrase, PasswordPolicy, Strength,
+};
 pub use http_serve::process_http_serve;
 pub use jwt::{process_jwt_sign, process_jwt_verify};
 pub use text::process_text_decrypt;
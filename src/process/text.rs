@@ -9,7 +9,7 @@ use chacha20poly1305::{
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 
-use crate::{get_reader, process_genpass, TextSignFormat};
+use crate::{get_reader, process_genpass, PasswordPolicy, TextSignFormat};
 
 pub trait TextSign {
     /// Sign the data from the reader and return the signature
@@ -161,7 +161,10 @@ impl KeyLoader for Blake3 {
 
 impl KeyGenerator for Blake3 {
     fn generate() -> Result<Vec<Vec<u8>>> {
-        let key = process_genpass(32, true, true, true, true)?;
+        let key = process_genpass(&PasswordPolicy {
+            length: 32,
+            ..Default::default()
+        })?;
         let key = key.as_bytes().to_vec();
         Ok(vec![key])
     }
@@ -258,7 +261,10 @@ mod tests {
 
     #[test]
     fn test_chacha20poly1305_encrypt_decrypt() -> Result<()> {
-        let key = process_genpass(32, true, true, true, true)?;
+        let key = process_genpass(&PasswordPolicy {
+            length: 32,
+            ..Default::default()
+        })?;
         let data = "hello world!";
         let encrypted = process_text_encrypt(data, &key)?;
         let decrypted = process_text_decrypt(&encrypted, &key)?;
@@ -1,5 +1,11 @@
 use anyhow::Result;
-use std::{fs::File, io::Read};
+use std::{
+    fs::File,
+    io::{BufWriter, IsTerminal, Read, Write},
+    process::{Command, Stdio},
+};
+#[cfg(test)]
+use std::{ops::Deref, path::Path};
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -9,3 +15,84 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     };
     Ok(reader)
 }
+
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(BufWriter::new(std::io::stdout()))
+    } else {
+        Box::new(BufWriter::new(File::create(output)?))
+    };
+    Ok(writer)
+}
+
+/// Print the content, through `$PAGER` (or `less`) when stdout is a terminal
+pub fn print_paged(content: &str) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        print!("{}", content);
+        return Ok(());
+    }
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -FRSX".to_string());
+    let mut parts = pager.split_whitespace();
+    let Some(program) = parts.next() else {
+        print!("{}", content);
+        return Ok(());
+    };
+    match Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // the pager may quit before reading everything
+                let _ = stdin.write_all(content.as_bytes());
+            }
+            child.wait()?;
+        }
+        Err(_) => print!("{}", content),
+    }
+    Ok(())
+}
+
+/// A unique path in the temp directory for tests, the file or directory is
+/// removed on drop so a failing assertion doesn't leave it behind
+#[cfg(test)]
+pub struct TempPath(String);
+
+#[cfg(test)]
+impl TempPath {
+    /// `rcli-<random><suffix>`, nothing is created
+    pub fn new(suffix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("rcli-{}{}", rand::random::<u32>(), suffix));
+        Self(path.to_string_lossy().to_string())
+    }
+}
+
+#[cfg(test)]
+impl Deref for TempPath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let path = Path::new(&self.0);
+        // the test may have failed before creating it
+        let _ = if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+    }
+}